@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, Query, Request},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use subtle::ConstantTimeEq;
+
+use crate::alerting;
+use crate::config::{ADMIN_ADDR, ADMIN_TOKEN};
+use crate::logger::{log_event, recent_logs};
+use crate::scheduler::{runner, state};
+
+const DEFAULT_LOG_TAIL: usize = 200;
+
+/// Serve the admin API on `ADMIN_ADDR` until the process exits.
+/// Does nothing when `ADMIN_TOKEN` is unset, so the API is never open.
+pub async fn serve() -> Result<()> {
+    if ADMIN_TOKEN.is_none() {
+        return Ok(());
+    }
+
+    let app = Router::new()
+        .route("/tasks", get(list_tasks))
+        .route("/tasks/{name}", get(get_task))
+        .route("/tasks/{name}/run", post(run_task))
+        .route("/tasks/{name}/pause", post(pause_task))
+        .route("/tasks/{name}/resume", post(resume_task))
+        .route("/tasks/{name}/interval", put(set_interval))
+        .route("/logs", get(tail_logs))
+        .layer(middleware::from_fn(require_token));
+
+    let listener = tokio::net::TcpListener::bind(ADMIN_ADDR.as_str())
+        .await
+        .with_context(|| format!("binding admin API to {}", *ADMIN_ADDR))?;
+    log_event("LAUNCH", &format!("admin API listening on {}", *ADMIN_ADDR), None);
+
+    axum::serve(listener, app).await.context("serving admin API")
+}
+
+/// Reject requests without `Authorization: Bearer <ADMIN_TOKEN>`.
+async fn require_token(req: Request, next: Next) -> Response {
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    // Constant-time, so response timing does not reveal how much of a guess matched
+    match (provided, ADMIN_TOKEN.as_deref()) {
+        (Some(p), Some(expected)) if bool::from(p.as_bytes().ct_eq(expected.as_bytes())) => next.run(req).await,
+        _ => error(StatusCode::UNAUTHORIZED, "missing or invalid admin token"),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn unknown_task(name: &str) -> Response {
+    error(StatusCode::NOT_FOUND, &format!("unknown task {name}"))
+}
+
+async fn list_tasks() -> Response {
+    Json(state::snapshot()).into_response()
+}
+
+async fn get_task(Path(name): Path<String>) -> Response {
+    match state::snapshot().into_iter().find(|t| t.name == name) {
+        Some(task) => Json(task).into_response(),
+        None => unknown_task(&name),
+    }
+}
+
+/// Run a task immediately, outside its schedule. Works while paused.
+async fn run_task(Path(name): Path<String>) -> Response {
+    let Some(node) = state::node(&name) else {
+        return unknown_task(&name);
+    };
+    log_event("ADMIN", &format!("manual run of {name}"), None);
+    tokio::spawn(runner::execute(node));
+    (StatusCode::ACCEPTED, Json(json!({ "triggered": name }))).into_response()
+}
+
+async fn pause_task(Path(name): Path<String>) -> Response {
+    if !state::set_paused(&name, true) {
+        return unknown_task(&name);
+    }
+    log_event("ADMIN", &format!("paused {name}"), None);
+    Json(json!({ "name": name, "paused": true })).into_response()
+}
+
+async fn resume_task(Path(name): Path<String>) -> Response {
+    if !state::set_paused(&name, false) {
+        return unknown_task(&name);
+    }
+    alerting::task_resumed(&name);
+    log_event("ADMIN", &format!("resumed {name}"), None);
+    Json(json!({ "name": name, "paused": false })).into_response()
+}
+
+#[derive(Deserialize)]
+struct IntervalBody {
+    interval: u64,
+}
+
+async fn set_interval(Path(name): Path<String>, Json(body): Json<IntervalBody>) -> Response {
+    if body.interval == 0 {
+        return error(StatusCode::BAD_REQUEST, "interval must be at least 1 second");
+    }
+    if body.interval > state::MAX_INTERVAL_SECS {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("interval must be at most {} seconds", state::MAX_INTERVAL_SECS),
+        );
+    }
+    if !state::set_interval(&name, body.interval) {
+        return unknown_task(&name);
+    }
+    log_event("ADMIN", &format!("set interval of {name} to {}s", body.interval), None);
+    Json(json!({ "name": name, "interval": body.interval })).into_response()
+}
+
+#[derive(Deserialize)]
+struct TailQuery {
+    limit: Option<usize>,
+}
+
+async fn tail_logs(Query(q): Query<TailQuery>) -> Response {
+    Json(recent_logs(q.limit.unwrap_or(DEFAULT_LOG_TAIL))).into_response()
+}
@@ -0,0 +1,290 @@
+pub mod webhook;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use tokio::time::{sleep, Duration};
+
+use crate::config::{ALERT_FAILURE_THRESHOLD, ALERT_STALE_SECS};
+use crate::logger::log_event;
+use crate::scheduler::failure::FailureKind;
+use crate::scheduler::node::FetchNode;
+use crate::scheduler::runs::TaskRun;
+use crate::scheduler::state;
+
+// How often the staleness watchdog looks at every task
+const STALE_CHECK_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    /// The task failed `ALERT_FAILURE_THRESHOLD` runs in a row.
+    ConsecutiveFailures,
+    /// The task has not succeeded for longer than its staleness window.
+    Stale,
+}
+
+impl AlertKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::ConsecutiveFailures => "consecutive_failures",
+            AlertKind::Stale => "stale",
+        }
+    }
+}
+
+/// A firing or resolved alert, ready to be rendered for a webhook.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub task: &'static str,
+    pub resolved: bool,
+    pub message: String,
+    pub consecutive_failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Per-task health, kept in memory. `firing` is what de-duplicates alerts:
+/// a condition only notifies when it starts and when it clears.
+#[derive(Debug)]
+struct TaskHealth {
+    /// When the engine started tracking the task, or it was last resumed.
+    since: DateTime<Utc>,
+    consecutive_failures: u32,
+    last_success: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    firing: HashSet<AlertKind>,
+}
+
+impl TaskHealth {
+    fn new(now: DateTime<Utc>) -> Self {
+        TaskHealth {
+            since: now,
+            consecutive_failures: 0,
+            last_success: None,
+            last_error: None,
+            firing: HashSet::new(),
+        }
+    }
+
+    fn alert(&self, kind: AlertKind, task: &'static str, resolved: bool, message: String, at: DateTime<Utc>) -> Alert {
+        Alert {
+            kind,
+            task,
+            resolved,
+            message,
+            consecutive_failures: self.consecutive_failures,
+            last_success: self.last_success,
+            last_error: self.last_error.clone(),
+            at,
+        }
+    }
+
+    /// Apply a finished run: a success resolves everything firing, a failure
+    /// fires `ConsecutiveFailures` once when the streak reaches `threshold`.
+    /// Returns the alerts to send. No I/O.
+    fn observe(
+        &mut self,
+        task: &'static str,
+        failure: Option<(FailureKind, String)>,
+        now: DateTime<Utc>,
+        threshold: u32,
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        match failure {
+            None => {
+                self.consecutive_failures = 0;
+                self.last_success = Some(now);
+                let mut resolved: Vec<AlertKind> = self.firing.drain().collect();
+                resolved.sort_by_key(|k| k.as_str());
+                for kind in resolved {
+                    alerts.push(self.alert(kind, task, true, format!("{task} is healthy again"), now));
+                }
+                self.last_error = None;
+            }
+            Some((kind, message)) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(message);
+                if self.consecutive_failures >= threshold && self.firing.insert(AlertKind::ConsecutiveFailures) {
+                    let message = format!(
+                        "{task} failed {} runs in a row (last failure: {kind})",
+                        self.consecutive_failures
+                    );
+                    alerts.push(self.alert(AlertKind::ConsecutiveFailures, task, false, message, now));
+                }
+            }
+        }
+        alerts
+    }
+
+    /// A paused task is not expected to run, so staleness counts from the resume.
+    fn resumed(&mut self, now: DateTime<Utc>) {
+        self.since = now;
+    }
+
+    /// Fire `Stale` once when nothing succeeded for `stale_after_secs` since
+    /// `since`. No I/O.
+    fn check_stale(&mut self, task: &'static str, now: DateTime<Utc>, stale_after_secs: i64) -> Option<Alert> {
+        let fresh_at = self.last_success.map_or(self.since, |at| at.max(self.since));
+        let age = (now - fresh_at).num_seconds();
+
+        if age <= stale_after_secs || !self.firing.insert(AlertKind::Stale) {
+            return None;
+        }
+        let message = match self.last_success {
+            Some(at) if at >= self.since => format!("{task} has not succeeded for {age}s"),
+            _ => format!("{task} has not succeeded since it was started or resumed {age}s ago"),
+        };
+        Some(self.alert(AlertKind::Stale, task, false, message, now))
+    }
+}
+
+static HEALTH: Lazy<Mutex<HashMap<&'static str, TaskHealth>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A task is stale after `ALERT_STALE_SECS`, but never sooner than two of its
+/// own intervals, so slow tasks do not alert between healthy runs.
+fn stale_after_secs(node: &FetchNode) -> i64 {
+    let interval = state::interval(node.name).unwrap_or(node.interval);
+    (*ALERT_STALE_SECS).max(i64::try_from(interval).unwrap_or(i64::MAX).saturating_mul(2))
+}
+
+/// Update a task's health with a finished run and notify on state changes.
+pub async fn observe_run(node: &'static FetchNode, run: &TaskRun) {
+    let now = Utc::now();
+    let failure = run.failure.as_ref().map(|f| (f.kind, f.message()));
+    let alerts = {
+        let mut health = HEALTH.lock().unwrap();
+        let h = health.entry(node.name).or_insert_with(|| TaskHealth::new(now));
+        h.observe(node.name, failure, now, *ALERT_FAILURE_THRESHOLD)
+    };
+
+    dispatch(alerts).await;
+}
+
+/// Restart the staleness clock of a task resumed from the admin API.
+pub fn task_resumed(task: &str) {
+    let now = Utc::now();
+    if let Some(h) = HEALTH.lock().unwrap().get_mut(task) {
+        h.resumed(now);
+    }
+}
+
+/// Fire a staleness alert for every task without a recent successful run.
+pub async fn check_staleness() {
+    let now = Utc::now();
+    let alerts = {
+        let mut health = HEALTH.lock().unwrap();
+        let mut alerts = Vec::new();
+
+        for node in inventory::iter::<FetchNode> {
+            // Paused on purpose from the admin API; staleness is expected
+            if state::is_paused(node.name) {
+                continue;
+            }
+            let h = health.entry(node.name).or_insert_with(|| TaskHealth::new(now));
+            alerts.extend(h.check_stale(node.name, now, stale_after_secs(node)));
+        }
+        alerts
+    };
+
+    dispatch(alerts).await;
+}
+
+/// Run `check_staleness` forever.
+pub async fn watch_staleness() {
+    loop {
+        sleep(Duration::from_secs(STALE_CHECK_INTERVAL_SECS)).await;
+        check_staleness().await;
+    }
+}
+
+async fn dispatch(alerts: Vec<Alert>) {
+    for alert in alerts {
+        let event = if alert.resolved { "RESOLVED" } else { "ALERT" };
+        log_event(event, &alert.message, None);
+        webhook::send(&alert).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const TASK: &str = "update_world_events";
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn failure() -> Option<(FailureKind, String)> {
+        Some((FailureKind::Upstream, "503 Service Unavailable".to_string()))
+    }
+
+    fn kinds(alerts: &[Alert]) -> Vec<(AlertKind, bool)> {
+        alerts.iter().map(|a| (a.kind, a.resolved)).collect()
+    }
+
+    #[test]
+    fn failure_streak_fires_once_at_threshold() {
+        let mut h = TaskHealth::new(at(0));
+
+        assert!(h.observe(TASK, failure(), at(1), 3).is_empty());
+        assert!(h.observe(TASK, failure(), at(2), 3).is_empty());
+        let fired = h.observe(TASK, failure(), at(3), 3);
+        assert_eq!(kinds(&fired), vec![(AlertKind::ConsecutiveFailures, false)]);
+        assert_eq!(fired[0].consecutive_failures, 3);
+        assert_eq!(fired[0].last_error.as_deref(), Some("503 Service Unavailable"));
+
+        // Further failures keep counting without notifying again
+        assert!(h.observe(TASK, failure(), at(4), 3).is_empty());
+        assert_eq!(h.consecutive_failures, 4);
+    }
+
+    #[test]
+    fn success_resolves_everything_firing() {
+        let mut h = TaskHealth::new(at(0));
+        h.observe(TASK, failure(), at(1), 1);
+        h.check_stale(TASK, at(1000), 600);
+
+        let resolved = h.observe(TASK, None, at(1001), 1);
+        assert_eq!(
+            kinds(&resolved),
+            vec![(AlertKind::ConsecutiveFailures, true), (AlertKind::Stale, true)]
+        );
+        assert_eq!(h.consecutive_failures, 0);
+        assert!(h.last_error.is_none());
+
+        // Nothing left to resolve
+        assert!(h.observe(TASK, None, at(1002), 1).is_empty());
+    }
+
+    #[test]
+    fn stale_fires_once_after_window() {
+        let mut h = TaskHealth::new(at(0));
+        h.observe(TASK, None, at(10), 3);
+
+        assert!(h.check_stale(TASK, at(610), 600).is_none());
+        let stale = h.check_stale(TASK, at(611), 600).unwrap();
+        assert_eq!((stale.kind, stale.resolved), (AlertKind::Stale, false));
+        assert_eq!(stale.message, format!("{TASK} has not succeeded for 601s"));
+        assert!(h.check_stale(TASK, at(1200), 600).is_none());
+    }
+
+    #[test]
+    fn resume_restarts_the_stale_clock() {
+        let mut h = TaskHealth::new(at(0));
+        h.observe(TASK, None, at(10), 3);
+
+        // Paused for an hour, then resumed
+        h.resumed(at(3600));
+        assert!(h.check_stale(TASK, at(3700), 600).is_none());
+        let stale = h.check_stale(TASK, at(4201), 600).unwrap();
+        assert_eq!(stale.message, format!("{TASK} has not succeeded since it was started or resumed 601s ago"));
+    }
+}
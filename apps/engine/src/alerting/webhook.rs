@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde_json::{json, Value};
+use tracing::{info_span, Instrument};
+
+use crate::alerting::Alert;
+use crate::config::ALERT_WEBHOOKS;
+use crate::logger::log_error;
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default()
+});
+
+static TARGETS: Lazy<Vec<WebhookTarget>> =
+    Lazy::new(|| ALERT_WEBHOOKS.iter().map(|raw| WebhookTarget::parse(raw)).collect());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// Discord `execute webhook` body with a single embed.
+    Discord,
+    /// Flat JSON object for anything else (Slack workflows, n8n, custom receivers).
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    pub format: WebhookFormat,
+    pub url: String,
+}
+
+impl WebhookTarget {
+    /// Parse `discord=<url>`, `json=<url>` or a bare `<url>` (treated as `json`).
+    fn parse(raw: &str) -> WebhookTarget {
+        match raw.split_once('=') {
+            Some(("discord", url)) => WebhookTarget { format: WebhookFormat::Discord, url: url.to_string() },
+            Some(("json", url)) => WebhookTarget { format: WebhookFormat::Json, url: url.to_string() },
+            _ => WebhookTarget { format: WebhookFormat::Json, url: raw.to_string() },
+        }
+    }
+}
+
+/// Deliver an alert to every configured webhook. Delivery failures are
+/// logged and otherwise ignored — alerting must never take a task down.
+pub async fn send(alert: &Alert) {
+    for target in TARGETS.iter() {
+        let res = post(target, alert)
+            .instrument(info_span!("alert.webhook", task = alert.task, kind = alert.kind.as_str()))
+            .await;
+        if let Err(e) = res {
+            log_error(&format!("alert webhook for {} failed", alert.task), &e, None);
+        }
+    }
+}
+
+async fn post(target: &WebhookTarget, alert: &Alert) -> Result<()> {
+    let body = match target.format {
+        WebhookFormat::Discord => discord_body(alert),
+        WebhookFormat::Json => json_body(alert),
+    };
+
+    CLIENT
+        .post(&target.url)
+        .json(&body)
+        .send()
+        .await
+        .context("posting alert webhook")?
+        .error_for_status()
+        .context("posting alert webhook")?;
+    Ok(())
+}
+
+fn discord_body(alert: &Alert) -> Value {
+    let (title, color) = if alert.resolved {
+        (format!("Recovered: {}", alert.task), 0x2ecc71)
+    } else {
+        (format!("Alert: {}", alert.task), 0xe74c3c)
+    };
+
+    let mut fields = vec![
+        json!({ "name": "Condition", "value": alert.kind.as_str(), "inline": true }),
+        json!({ "name": "Consecutive failures", "value": alert.consecutive_failures.to_string(), "inline": true }),
+        json!({ "name": "Last success", "value": alert.last_success.map(|t| t.to_rfc3339()).unwrap_or_else(|| "never".to_string()), "inline": true }),
+    ];
+    if let Some(err) = &alert.last_error {
+        // Discord rejects field values over 1024 chars
+        let err: String = err.chars().take(1000).collect();
+        fields.push(json!({ "name": "Last error", "value": format!("```{err}```") }));
+    }
+
+    json!({
+        "username": "Wynnpool Engine",
+        "embeds": [{
+            "title": title,
+            "description": alert.message,
+            "color": color,
+            "timestamp": alert.at.to_rfc3339(),
+            "fields": fields,
+        }],
+    })
+}
+
+fn json_body(alert: &Alert) -> Value {
+    json!({
+        "event": if alert.resolved { "alert.resolved" } else { "alert.firing" },
+        "alert": alert.kind.as_str(),
+        "task": alert.task,
+        "message": alert.message,
+        "consecutiveFailures": alert.consecutive_failures,
+        "lastSuccessAt": alert.last_success.map(|t| t.to_rfc3339()),
+        "lastError": alert.last_error,
+        "at": alert.at.to_rfc3339(),
+    })
+}
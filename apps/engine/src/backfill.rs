@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::FindOptions;
+use mongodb::Database;
+use serde_json::Value;
+use tracing::{info_span, Instrument};
+
+use crate::logger::log_event;
+use crate::mongo_client::{database, insert_ignoring_duplicates};
+use crate::tasks::event_predictions::{ensure_history, extract_i64, HISTORY_COLLECTION};
+use crate::tasks::world_events::schedule::parse_schedule_to_ms;
+
+// Documents per `insert` command; well under the server's batch limits
+const INSERT_BATCH: usize = 1000;
+
+// Archived times are often rounded to the second or minute; anything this
+// close to a known occurrence of the same event is that occurrence
+const SAME_OCCURRENCE_MS: i64 = 5 * 60 * 1000;
+
+/// One known occurrence of a world event.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Occurrence {
+    pub internal_name: String,
+    pub at_ms: i64,
+}
+
+/// Occurrences from archived JSON: raw `/v3/map/world-events` responses,
+/// `world_event_schedules` exports (`scheduleAt`, extended JSON dates
+/// included) or `world_event_history` exports (`datetime_utc`). Arrays may be
+/// nested (several snapshots in one file) and the file may hold one JSON
+/// document per line. Objects without a name or a readable time are skipped.
+pub fn parse_json_occurrences(text: &str) -> Result<Vec<Occurrence>> {
+    let values: Vec<Value> = match serde_json::from_str(text) {
+        Ok(v) => vec![v],
+        Err(_) => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("parsing JSON on line {}", i + 1)))
+            .collect::<Result<_>>()?,
+    };
+
+    let mut out = Vec::new();
+    for value in &values {
+        collect_json(value, &mut out);
+    }
+    Ok(out)
+}
+
+fn collect_json(value: &Value, out: &mut Vec<Occurrence>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| collect_json(v, out)),
+        Value::Object(o) => {
+            let Some(name) = o.get("internalName").and_then(Value::as_str).filter(|n| !n.is_empty()) else {
+                return;
+            };
+            let at_ms = ["datetime_utc", "scheduleAt", "schedule"]
+                .iter()
+                .find_map(|k| o.get(*k).and_then(json_time_ms));
+            if let Some(at_ms) = at_ms {
+                out.push(Occurrence { internal_name: name.to_string(), at_ms });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Epoch millis from a schedule string, a number (fractional seconds
+/// included), or an extended JSON date (`{"$date": ...}`, `{"$numberLong": ...}`).
+fn json_time_ms(value: &Value) -> Option<i64> {
+    match value {
+        Value::String(s) => parse_schedule_to_ms(s),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => parse_schedule_to_ms(&n.to_string()),
+            // Same seconds/millis heuristic as `parse_schedule_to_ms`
+            None => n.as_f64().map(|f| if f < 1e11 { (f * 1000.0).round() as i64 } else { f.round() as i64 }),
+        },
+        Value::Object(o) => o.get("$date").or_else(|| o.get("$numberLong")).and_then(json_time_ms),
+        _ => None,
+    }
+}
+
+/// Occurrences from a CSV with an `internalName` column and a time column
+/// (`datetime_utc`, `scheduleAt` or `schedule`), RFC 3339 or epoch
+/// seconds/millis. Fields are not quoted; rows with a bad time are an error
+/// rather than silently dropped.
+pub fn parse_csv_occurrences(text: &str) -> Result<Vec<Occurrence>> {
+    let mut lines = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let name_col = columns
+        .iter()
+        .position(|c| *c == "internalName")
+        .context("CSV header has no internalName column")?;
+    let time_col = ["datetime_utc", "scheduleAt", "schedule"]
+        .iter()
+        .find_map(|k| columns.iter().position(|c| c == k))
+        .context("CSV header has no datetime_utc, scheduleAt or schedule column")?;
+
+    lines
+        .map(|(i, line)| {
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let name = cells.get(name_col).filter(|n| !n.is_empty());
+            let at_ms = cells.get(time_col).and_then(|t| parse_schedule_to_ms(t));
+            match (name, at_ms) {
+                (Some(name), Some(at_ms)) => Ok(Occurrence { internal_name: name.to_string(), at_ms }),
+                _ => bail!("line {}: expected an event name and a time, got {line:?}", i + 1),
+            }
+        })
+        .collect()
+}
+
+/// History docs for occurrences that have already happened. An occurrence
+/// within `SAME_OCCURRENCE_MS` of a `known` time for the same event, or of an
+/// earlier one in this import, is the same occurrence and is dropped: a second
+/// row would shrink the mean interval `forecast` works from. Pure.
+pub fn history_docs(occurrences: Vec<Occurrence>, known: &BTreeMap<String, Vec<i64>>, now_ms: i64) -> Vec<Document> {
+    let past: BTreeSet<Occurrence> = occurrences.into_iter().filter(|o| o.at_ms <= now_ms).collect();
+    let mut seen: BTreeMap<String, BTreeSet<i64>> = known
+        .iter()
+        .map(|(name, times)| (name.clone(), times.iter().copied().collect()))
+        .collect();
+
+    let mut docs = Vec::new();
+    for o in past {
+        let times = seen.entry(o.internal_name.clone()).or_default();
+        let window = o.at_ms - SAME_OCCURRENCE_MS..=o.at_ms + SAME_OCCURRENCE_MS;
+        if times.range(window).next().is_some() {
+            continue;
+        }
+        times.insert(o.at_ms);
+        docs.push(doc! { "internalName": o.internal_name, "datetime_utc": o.at_ms, "source": "imported" });
+    }
+    docs
+}
+
+/// Recorded history times for the given events.
+async fn known_times(db: &Database, names: Vec<String>) -> Result<BTreeMap<String, Vec<i64>>> {
+    let opts = FindOptions::builder()
+        .projection(doc! { "_id": 0, "internalName": 1, "datetime_utc": 1 })
+        .build();
+    let docs: Vec<Document> = async {
+        db.collection::<Document>(HISTORY_COLLECTION)
+            .find(doc! { "internalName": { "$in": names } }, opts)
+            .await?
+            .try_collect()
+            .await
+    }
+    .instrument(info_span!("mongo.find", collection = HISTORY_COLLECTION))
+    .await
+    .context("loading known world event history")?;
+
+    let mut known: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for d in &docs {
+        if let (Ok(name), Some(at_ms)) = (d.get_str("internalName"), extract_i64(d, "datetime_utc")) {
+            known.entry(name.to_string()).or_default().push(at_ms);
+        }
+    }
+    Ok(known)
+}
+
+/// `wynnpool-engine backfill <file>...`: import known event times into
+/// `world_event_history`. Files ending in `.csv` are read as CSV, everything
+/// else as JSON. Occurrences already recorded, to within `SAME_OCCURRENCE_MS`,
+/// are skipped.
+pub async fn run(paths: &[String]) -> Result<()> {
+    if paths.is_empty() {
+        bail!("usage: wynnpool-engine backfill <snapshot.json|times.csv>...");
+    }
+    let whole_start = Instant::now();
+
+    let mut occurrences = Vec::new();
+    for path in paths {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+        let is_csv = Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+        let parsed = if is_csv {
+            parse_csv_occurrences(&text)
+        } else {
+            parse_json_occurrences(&text)
+        }
+        .with_context(|| format!("parsing {path}"))?;
+        log_event("TASK", &format!("backfill: {} occurrences in {path}", parsed.len()), None);
+        occurrences.extend(parsed);
+    }
+
+    let now_ms: i64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let found = occurrences.len();
+
+    let db = database().await?;
+    ensure_history(&db).await?;
+
+    let names: BTreeSet<String> = occurrences.iter().map(|o| o.internal_name.clone()).collect();
+    let known = known_times(&db, names.into_iter().collect()).await?;
+    let docs = history_docs(occurrences, &known, now_ms);
+    let new_count = docs.len();
+
+    let mut imported = 0usize;
+    let mut batches = docs.into_iter().peekable();
+    while batches.peek().is_some() {
+        let batch: Vec<Document> = batches.by_ref().take(INSERT_BATCH).collect();
+        let count = batch.len();
+        imported += insert_ignoring_duplicates(&db, HISTORY_COLLECTION, batch)
+            .instrument(info_span!("mongo.append_history", collection = HISTORY_COLLECTION, count))
+            .await
+            .context("importing world event history")?;
+    }
+
+    log_event(
+        "SUMMARY",
+        &format!(
+            "backfill: found={}, new={}, imported={}",
+            found,
+            new_count,
+            imported,
+        ),
+        Some(whole_start.elapsed()),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_MS: i64 = 1_700_000_000_000;
+
+    fn occ(name: &str, at_ms: i64) -> Occurrence {
+        Occurrence { internal_name: name.into(), at_ms }
+    }
+
+    #[test]
+    fn reads_api_snapshots_and_collection_exports() {
+        let text = r#"[
+            [{ "internalName": "Prelude to Annihilation", "schedule": "2023-11-14T10:00:00Z", "name": "x" }],
+            { "internalName": "Corrupted Spire", "scheduleAt": { "$date": { "$numberLong": "1699000000000" } } },
+            { "internalName": "Corrupted Spire", "datetime_utc": 1698000000000 },
+            { "internalName": "No Time" },
+            { "name": "No Name", "schedule": "2023-11-14T10:00:00Z" }
+        ]"#;
+        assert_eq!(
+            parse_json_occurrences(text).unwrap(),
+            vec![
+                occ("Prelude to Annihilation", 1_699_956_000_000),
+                occ("Corrupted Spire", 1_699_000_000_000),
+                occ("Corrupted Spire", 1_698_000_000_000),
+            ]
+        );
+
+        let lines = "{\"internalName\": \"A\", \"datetime_utc\": 1}\n\n{\"internalName\": \"B\", \"datetime_utc\": 2}\n";
+        assert_eq!(parse_json_occurrences(lines).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn reads_csv_and_rejects_bad_rows() {
+        let text = "datetime_utc,internalName\n2023-11-14T10:00:00Z,Prelude to Annihilation\n1699000000,Corrupted Spire\n";
+        assert_eq!(
+            parse_csv_occurrences(text).unwrap(),
+            vec![occ("Prelude to Annihilation", 1_699_956_000_000), occ("Corrupted Spire", 1_699_000_000_000)]
+        );
+
+        let bad = "internalName,datetime_utc\nCorrupted Spire,soon\n";
+        assert!(parse_csv_occurrences(bad).is_err());
+        assert!(parse_csv_occurrences("name,when\n").is_err());
+    }
+
+    #[test]
+    fn reads_fractional_epoch_times() {
+        let text = r#"[{ "internalName": "A", "datetime_utc": 1699000000.25 }, { "internalName": "B", "schedule": 1699000000250.0 }]"#;
+        assert_eq!(
+            parse_json_occurrences(text).unwrap(),
+            vec![occ("A", 1_699_000_000_250), occ("B", 1_699_000_000_250)]
+        );
+    }
+
+    #[test]
+    fn history_docs_are_past_distinct_and_marked_imported() {
+        let docs = history_docs(
+            vec![occ("A", NOW_MS - 10), occ("A", NOW_MS - 10), occ("A", NOW_MS + 10), occ("B", NOW_MS - 5)],
+            &BTreeMap::new(),
+            NOW_MS,
+        );
+        assert_eq!(docs.len(), 2);
+        assert!(docs.iter().all(|d| d.get_str("source") == Ok("imported")));
+    }
+
+    #[test]
+    fn rounded_times_collapse_into_known_occurrences() {
+        const HOUR_MS: i64 = 60 * 60 * 1000;
+        let observed = NOW_MS - 2 * HOUR_MS + 37_123;
+        let known = BTreeMap::from([("A".to_string(), vec![observed])]);
+
+        let docs = history_docs(
+            vec![
+                // The observed occurrence, rounded to the minute
+                occ("A", NOW_MS - 2 * HOUR_MS),
+                // Another import of an hour later, once to the second, once to the minute
+                occ("A", NOW_MS - HOUR_MS + 12_000),
+                occ("A", NOW_MS - HOUR_MS),
+                // Same time, different event
+                occ("B", NOW_MS - 2 * HOUR_MS),
+            ],
+            &known,
+            NOW_MS,
+        );
+
+        let times: Vec<(&str, i64)> = docs
+            .iter()
+            .map(|d| (d.get_str("internalName").unwrap(), d.get_i64("datetime_utc").unwrap()))
+            .collect();
+        assert_eq!(times, vec![("A", NOW_MS - HOUR_MS), ("B", NOW_MS - 2 * HOUR_MS)]);
+    }
+}
@@ -1,8 +1,112 @@
 use once_cell::sync::Lazy;
 use std::env;
+use std::str::FromStr;
 
 pub static REDIS_URL: Lazy<String> =
     Lazy::new(|| env::var("REDIS_URL").expect("REDIS_URL not set"));
 
 pub static MONGODB_URI: Lazy<String> =
-    Lazy::new(|| env::var("MONGODB_URI").expect("MONGODB_URI not set"));
\ No newline at end of file
+    Lazy::new(|| env::var("MONGODB_URI").expect("MONGODB_URI not set"));
+
+// How long scheduler run records are kept in `engine_task_runs` (default 7 days)
+pub static TASK_RUN_TTL_SECS: Lazy<i64> =
+    Lazy::new(|| env_or("TASK_RUN_TTL_SECS", 60 * 60 * 24 * 7));
+
+// OTLP gRPC collector for task traces, e.g. http://localhost:4317 (tracing is off when unset)
+pub static OTLP_ENDPOINT: Lazy<Option<String>> =
+    Lazy::new(|| env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().filter(|v| !v.trim().is_empty()));
+
+// Untouched wynncraft_servers docs expire after this long (default 12 hours)
+pub static SERVER_DATA_TTL_SECS: Lazy<i64> =
+    Lazy::new(|| env_or("SERVER_DATA_TTL_SECS", 60 * 60 * 12));
+
+// Delete a server doc once it has been offline for longer than this (default 1 minute)
+pub static OFFLINE_DELETE_SECS: Lazy<i64> = Lazy::new(|| env_or("OFFLINE_DELETE_SECS", 60));
+
+// Consecutive polls a server must be absent before it is marked offline (default 2)
+pub static SERVER_OFFLINE_AFTER_POLLS: Lazy<i64> =
+    Lazy::new(|| env_or("SERVER_OFFLINE_AFTER_POLLS", 2));
+
+// Redis key holding the latest computed server status JSON
+pub static SERVER_STATUS_KEY: Lazy<String> =
+    Lazy::new(|| env_or("SERVER_STATUS_KEY", "wynnpool:server_status".to_string()));
+
+// Redis pub/sub channel notified whenever the server status changes
+pub static SERVER_STATUS_CHANNEL: Lazy<String> =
+    Lazy::new(|| env_or("SERVER_STATUS_CHANNEL", "wynnpool:server_status:updates".to_string()));
+
+// A server status snapshot whose player total falls by more than this percentage
+// of the last accepted one is treated as a partial upstream response (default 40)
+pub static ANOMALY_DROP_PERCENT: Lazy<f64> = Lazy::new(|| env_or("ANOMALY_DROP_PERCENT", 40.0));
+
+// Drops are only judged once the last accepted total reaches this many players (default 100)
+pub static ANOMALY_MIN_PLAYERS: Lazy<i64> = Lazy::new(|| env_or("ANOMALY_MIN_PLAYERS", 100));
+
+// Consecutive suspect snapshots after which they are accepted as real (default 3)
+pub static ANOMALY_STABLE_POLLS: Lazy<i64> = Lazy::new(|| env_or("ANOMALY_STABLE_POLLS", 3));
+
+// How long recorded server status anomalies are kept (default 30 days)
+pub static ANOMALY_TTL_SECS: Lazy<i64> =
+    Lazy::new(|| env_or("ANOMALY_TTL_SECS", 60 * 60 * 24 * 30));
+
+// Redis pub/sub channel for watched players logging on, off or changing world
+pub static WATCHLIST_CHANNEL: Lazy<String> =
+    Lazy::new(|| env_or("WATCHLIST_CHANNEL", "wynnpool:player_presence".to_string()));
+
+// Redis stream receiving world event changelog notifications
+pub static WORLD_EVENT_STREAM: Lazy<String> =
+    Lazy::new(|| env_or("WORLD_EVENT_STREAM", "wynnpool:world_event_changes".to_string()));
+
+// Approximate cap on the world event notification stream length (default 1000)
+pub static WORLD_EVENT_STREAM_MAXLEN: Lazy<usize> = Lazy::new(|| env_or("WORLD_EVENT_STREAM_MAXLEN", 1000));
+
+// Comma-separated world event fields whose changes are announced, e.g. `rewardPerLevel,level` (default every field)
+pub static WORLD_EVENT_NOTIFY_FIELDS: Lazy<Vec<String>> = Lazy::new(|| env_list("WORLD_EVENT_NOTIFY_FIELDS"));
+
+// Raw per-tick player counts are kept this long (default 48 hours)
+pub static PLAYER_COUNT_RAW_RETENTION_SECS: Lazy<u64> =
+    Lazy::new(|| env_or("PLAYER_COUNT_RAW_RETENTION_SECS", 60 * 60 * 48));
+
+// Width of a downsampled player count bucket (default 10 minutes)
+pub static PLAYER_COUNT_BUCKET_SECS: Lazy<i64> =
+    Lazy::new(|| env_or("PLAYER_COUNT_BUCKET_SECS", 60 * 10));
+
+// Downsampled player counts are kept this long (default 90 days)
+pub static PLAYER_COUNT_DOWNSAMPLED_RETENTION_SECS: Lazy<u64> =
+    Lazy::new(|| env_or("PLAYER_COUNT_DOWNSAMPLED_RETENTION_SECS", 60 * 60 * 24 * 90));
+
+// Comma-separated alert webhooks, each `discord=<url>` or `json=<url>` (bare URLs are `json`)
+pub static ALERT_WEBHOOKS: Lazy<Vec<String>> = Lazy::new(|| env_list("ALERT_WEBHOOKS"));
+
+// Consecutive failed runs before a task alerts
+pub static ALERT_FAILURE_THRESHOLD: Lazy<u32> =
+    Lazy::new(|| env_or("ALERT_FAILURE_THRESHOLD", 5));
+
+// Seconds without a successful run before a task's dataset counts as stale
+pub static ALERT_STALE_SECS: Lazy<i64> = Lazy::new(|| env_or("ALERT_STALE_SECS", 60 * 10));
+
+// Bearer token for the admin HTTP API (the API is not started when unset)
+pub static ADMIN_TOKEN: Lazy<Option<String>> =
+    Lazy::new(|| env::var("ADMIN_TOKEN").ok().filter(|v| !v.trim().is_empty()));
+
+// Listen address of the admin HTTP API
+pub static ADMIN_ADDR: Lazy<String> =
+    Lazy::new(|| env::var("ADMIN_ADDR").unwrap_or_else(|_| "127.0.0.1:8787".to_string()));
+
+/// Read an optional env var, falling back to `default` when unset or unparsable.
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Read an optional comma-separated env var; empty entries are dropped.
+fn env_list(key: &str) -> Vec<String> {
+    env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
@@ -1,10 +1,16 @@
 use chrono::Local;
 use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::sync::Mutex;
 use std::time::Duration;
 
-/// Simple structured log entry (optional)
-#[derive(Debug)]
+// Entries kept in memory for the admin API's log tail
+const LOG_BUFFER_CAPACITY: usize = 2000;
+
+/// Simple structured log entry
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LogEntry {
     pub timestamp: String,
     pub event: String,
@@ -12,8 +18,9 @@ pub struct LogEntry {
     pub elapsed_ms: Option<u128>,
 }
 
-/// Optional in-memory log store (you can delete this if you don't need it)
-static LOGS: Lazy<Mutex<Vec<LogEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+/// In-memory ring buffer of the most recent log entries
+static LOGS: Lazy<Mutex<VecDeque<LogEntry>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)));
 
 /// Log an event like:
 /// 2025/11/18 16:51:12  LAUNCH  Wynnpool engine started      0ms
@@ -27,10 +34,13 @@ pub fn log_event(event: &str, message: &str, elapsed: Option<Duration>) {
         .map(|ms| format!("{ms}ms"))
         .unwrap_or_else(|| "-".to_string());
 
-    // store in memory (optional)
+    // store in memory, dropping the oldest entry once full
     {
         let mut guard = LOGS.lock().unwrap();
-        guard.push(LogEntry {
+        if guard.len() >= LOG_BUFFER_CAPACITY {
+            guard.pop_front();
+        }
+        guard.push_back(LogEntry {
             timestamp: timestamp.clone(),
             event: event.to_string(),
             message: message.to_string(),
@@ -44,3 +54,20 @@ pub fn log_event(event: &str, message: &str, elapsed: Option<Duration>) {
         timestamp, event, message, elapsed_str
     );
 }
+
+/// Log an error followed by its full cause chain, one `CAUSE` line per source:
+/// 2025/11/18 16:51:12  ERROR    update_world_events failed [decode]    1204ms
+/// 2025/11/18 16:51:12  CAUSE    decoding world events response        -
+/// 2025/11/18 16:51:12  CAUSE    error decoding response body          -
+pub fn log_error(message: &str, err: &anyhow::Error, elapsed: Option<Duration>) {
+    log_event("ERROR", message, elapsed);
+    for cause in err.chain() {
+        log_event("CAUSE", &cause.to_string(), None);
+    }
+}
+
+/// The last `limit` buffered entries, oldest first.
+pub fn recent_logs(limit: usize) -> Vec<LogEntry> {
+    let guard = LOGS.lock().unwrap();
+    guard.iter().skip(guard.len().saturating_sub(limit)).cloned().collect()
+}
@@ -1,36 +1,55 @@
+mod admin;
+mod alerting;
+mod backfill;
 mod config;
+mod mongo_client;
 mod redis_client;
 mod scheduler;
 mod tasks;
+mod telemetry;
 mod logger;
 
-use std::future;
+use std::{env, future, process};
 
 use dotenvy::dotenv;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
 use scheduler::node::FetchNode;
-use crate::logger::log_event;
+use crate::logger::{log_error, log_event};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
+    // One-off commands run instead of the scheduler
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("backfill") {
+        if let Err(e) = backfill::run(&args[1..]).await {
+            log_error("backfill failed", &e, None);
+            process::exit(1);
+        }
+        return;
+    }
+
     log_event("LAUNCH", "Wynnpool engine started", Some(Duration::from_millis(0)));
 
+    match telemetry::init() {
+        Ok(Some(endpoint)) => log_event("LAUNCH", &format!("exporting traces to {endpoint}"), None),
+        Ok(None) => {}
+        Err(e) => log_error("tracing disabled", &e, None),
+    }
+
     for node in inventory::iter::<FetchNode> {
-        let interval = node.interval;
-        let callback = node.callback;
-        let name = node.name;
-
-        tokio::spawn(async move {
-            loop {
-                log_event("TASK", &format!("running {}", name), None);
-                (callback)();
-                sleep(Duration::from_secs(interval)).await;
-            }
-        });
+        tokio::spawn(scheduler::runner::run_forever(node));
     }
 
+    tokio::spawn(alerting::watch_staleness());
+
+    tokio::spawn(async {
+        if let Err(e) = admin::serve().await {
+            log_error("admin API stopped", &e, None);
+        }
+    });
+
     // Keep main alive forever
     future::pending::<()>().await;
 }
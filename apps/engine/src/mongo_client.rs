@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use mongodb::{bson::{doc, Document}, options::ClientOptions, Client, Database};
+use tokio::sync::OnceCell;
+
+use crate::scheduler::failure::{WriteConcernFailed, WriteErrors};
+
+static CLIENT: OnceCell<Client> = OnceCell::const_new();
+
+// Server error code for a unique index violation
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Shared MongoDB client, connected lazily on first use.
+pub async fn mongo_client() -> Result<&'static Client> {
+    CLIENT
+        .get_or_try_init(|| async {
+            let mut client_options = ClientOptions::parse(crate::config::MONGODB_URI.as_str())
+                .await
+                .context("parsing MONGODB_URI")?;
+            client_options.app_name = Some("wynnpool-engine".to_string());
+            Client::with_options(client_options).context("creating MongoDB client")
+        })
+        .await
+}
+
+/// The `wynnpool` database every task writes to.
+pub async fn database() -> Result<Database> {
+    Ok(mongo_client().await?.database("wynnpool"))
+}
+
+/// Run a raw write command (`update`, `delete`, `insert`) carrying many
+/// statements in one round trip. The server reports per-statement failures in
+/// `writeErrors` and write concern failures in `writeConcernError` rather than
+/// failing the command, so surface those as errors.
+pub async fn run_write_command(db: &Database, command: Document) -> Result<Document> {
+    let reply = db.run_command(command, None).await?;
+    check_write_reply(&reply, &[])?;
+    Ok(reply)
+}
+
+/// Insert `docs` in one unordered `insert` command, skipping documents that
+/// collide with a unique index. Returns how many were actually inserted; any
+/// other write error fails the call.
+pub async fn insert_ignoring_duplicates(db: &Database, collection: &str, docs: Vec<Document>) -> Result<usize> {
+    if docs.is_empty() {
+        return Ok(0);
+    }
+    let reply = db
+        .run_command(doc! { "insert": collection, "documents": docs, "ordered": false }, None)
+        .await?;
+    check_write_reply(&reply, &[DUPLICATE_KEY])?;
+    Ok(reply.get_i32("n").unwrap_or(0) as usize)
+}
+
+/// Fail on a write command reply's `writeErrors` (other than codes in
+/// `ignored`) or `writeConcernError`.
+fn check_write_reply(reply: &Document, ignored: &[i32]) -> Result<()> {
+    if let Ok(errors) = reply.get_array("writeErrors") {
+        let other: Vec<&Document> = errors
+            .iter()
+            .filter_map(|e| e.as_document())
+            .filter(|e| !e.get_i32("code").is_ok_and(|code| ignored.contains(&code)))
+            .collect();
+        if let Some(first) = other.first() {
+            return Err(WriteErrors {
+                count: other.len(),
+                first: first.get_str("errmsg").unwrap_or("unknown write error").to_string(),
+            }
+            .into());
+        }
+    }
+    if let Ok(concern) = reply.get_document("writeConcernError") {
+        let message = concern.get_str("errmsg").unwrap_or("unknown write concern error");
+        return Err(WriteConcernFailed(message.to_string()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_errors_fail_unless_ignored() {
+        let reply = doc! { "n": 1, "writeErrors": [{ "index": 1, "code": DUPLICATE_KEY, "errmsg": "E11000" }] };
+        assert!(check_write_reply(&reply, &[DUPLICATE_KEY]).is_ok());
+        assert!(check_write_reply(&reply, &[]).unwrap_err().is::<WriteErrors>());
+    }
+
+    #[test]
+    fn write_concern_error_fails() {
+        let reply = doc! { "n": 3, "writeConcernError": { "code": 64, "errmsg": "waiting for replication timed out" } };
+        let err = check_write_reply(&reply, &[DUPLICATE_KEY]).unwrap_err();
+        assert_eq!(err.to_string(), "write concern error: waiting for replication timed out");
+    }
+}
@@ -1,19 +1,51 @@
 use redis::AsyncCommands;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use tracing::{info_span, Instrument};
 
 pub async fn redis_conn() -> Result<redis::aio::Connection> {
-    let client = redis::Client::open(crate::config::REDIS_URL.as_str())?;
-    Ok(client.get_tokio_connection().await?)
+    let client = redis::Client::open(crate::config::REDIS_URL.as_str()).context("parsing REDIS_URL")?;
+    client.get_tokio_connection().await.context("connecting to Redis")
 }
 
 pub async fn set_json(key: &str, value: serde_json::Value) -> Result<()> {
     let mut conn = redis_conn().await?;
-    conn.set::<_, _, ()>(key, value.to_string()).await?;
+    conn.set::<_, _, ()>(key, value.to_string())
+        .instrument(info_span!("redis.set", key))
+        .await
+        .with_context(|| format!("writing Redis key {key}"))?;
     Ok(())
 }
 
 pub async fn get_json(key: &str) -> Result<Option<serde_json::Value>> {
     let mut conn = redis_conn().await?;
-    let raw: Option<String> = conn.get(key).await?;
-    Ok(raw.map(|s| serde_json::from_str(&s).unwrap()))
+    let raw: Option<String> = conn
+        .get(key)
+        .instrument(info_span!("redis.get", key))
+        .await
+        .with_context(|| format!("reading Redis key {key}"))?;
+    raw.map(|s| serde_json::from_str(&s).with_context(|| format!("decoding Redis key {key}")))
+        .transpose()
+}
+
+pub async fn publish_json(channel: &str, value: serde_json::Value) -> Result<()> {
+    let mut conn = redis_conn().await?;
+    conn.publish::<_, _, ()>(channel, value.to_string())
+        .instrument(info_span!("redis.publish", channel))
+        .await
+        .with_context(|| format!("publishing to Redis channel {channel}"))?;
+    Ok(())
+}
+
+/// Append an entry to a stream, trimming it to roughly `maxlen` entries.
+pub async fn stream_add(stream: &str, maxlen: usize, fields: &[(&str, String)]) -> Result<String> {
+    let mut conn = redis_conn().await?;
+    let mut cmd = redis::cmd("XADD");
+    cmd.arg(stream).arg("MAXLEN").arg("~").arg(maxlen).arg("*");
+    for (field, value) in fields {
+        cmd.arg(*field).arg(value);
+    }
+    cmd.query_async(&mut conn)
+        .instrument(info_span!("redis.xadd", stream))
+        .await
+        .with_context(|| format!("appending to Redis stream {stream}"))
 }
@@ -0,0 +1,163 @@
+use std::fmt;
+
+/// Coarse classification of why a task run failed, recorded with each failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The upstream API could not be reached or answered with an error status.
+    Upstream,
+    /// The upstream answered, but the body was not what we expected.
+    Decode,
+    /// MongoDB or Redis rejected or failed an operation.
+    Storage,
+    /// Something took too long.
+    Timeout,
+    /// Nothing in the cause chain is recognised: most likely a bug in the
+    /// engine itself rather than a dependency misbehaving.
+    Internal,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Upstream => "upstream",
+            FailureKind::Decode => "decode",
+            FailureKind::Storage => "storage",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Internal => "internal",
+        }
+    }
+
+    /// Classify by the first recognised error in the cause chain.
+    /// Errors we know nothing about are `Internal`.
+    pub fn classify(err: &anyhow::Error) -> FailureKind {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                return if e.is_timeout() {
+                    FailureKind::Timeout
+                } else if e.is_decode() {
+                    FailureKind::Decode
+                } else {
+                    FailureKind::Upstream
+                };
+            }
+            if cause.is::<tokio::time::error::Elapsed>() {
+                return FailureKind::Timeout;
+            }
+            if cause.is::<serde_json::Error>() || cause.is::<UnexpectedPayload>() {
+                return FailureKind::Decode;
+            }
+            if cause.is::<mongodb::error::Error>() || cause.is::<WriteErrors>() || cause.is::<WriteConcernFailed>() {
+                return FailureKind::Storage;
+            }
+            if let Some(e) = cause.downcast_ref::<redis::RedisError>() {
+                return if e.is_timeout() {
+                    FailureKind::Timeout
+                } else {
+                    FailureKind::Storage
+                };
+            }
+        }
+        FailureKind::Internal
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The upstream body parsed, but is missing a field or has the wrong shape.
+#[derive(Debug)]
+pub struct UnexpectedPayload(pub &'static str);
+
+impl fmt::Display for UnexpectedPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for UnexpectedPayload {}
+
+/// MongoDB accepted a multi-statement write command but rejected some statements.
+#[derive(Debug)]
+pub struct WriteErrors {
+    pub count: usize,
+    pub first: String,
+}
+
+impl fmt::Display for WriteErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} write error(s), first: {}", self.count, self.first)
+    }
+}
+
+impl std::error::Error for WriteErrors {}
+
+/// The statements were applied, but not acknowledged by the requested write
+/// concern (e.g. not replicated in time).
+#[derive(Debug)]
+pub struct WriteConcernFailed(pub String);
+
+impl fmt::Display for WriteConcernFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write concern error: {}", self.0)
+    }
+}
+
+impl std::error::Error for WriteConcernFailed {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reqwest_errors_split_into_upstream_and_decode() {
+        let request = reqwest::Client::new().get("not a url").send().await.unwrap_err();
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(request)), FailureKind::Upstream);
+
+        let response = reqwest::Response::from(axum::http::Response::new("<html>"));
+        let decode = response.json::<serde_json::Value>().await.unwrap_err();
+        let err = anyhow::Error::from(decode).context("decoding /v3/player response");
+        assert_eq!(FailureKind::classify(&err), FailureKind::Decode);
+    }
+
+    #[tokio::test]
+    async fn elapsed_is_timeout() {
+        let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>()).await.unwrap_err();
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(elapsed)), FailureKind::Timeout);
+    }
+
+    #[test]
+    fn payload_errors_are_decode() {
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(json)), FailureKind::Decode);
+
+        let payload = anyhow::Error::from(UnexpectedPayload("Invalid players format"));
+        assert_eq!(FailureKind::classify(&payload), FailureKind::Decode);
+    }
+
+    #[test]
+    fn database_errors_are_storage() {
+        let mongo = mongodb::error::Error::custom("connection pool cleared");
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(mongo)), FailureKind::Storage);
+
+        let writes = Err::<(), _>(WriteErrors { count: 1, first: "E11000".into() }).context("upserting servers");
+        assert_eq!(FailureKind::classify(&writes.unwrap_err()), FailureKind::Storage);
+
+        let redis = redis::RedisError::from((redis::ErrorKind::ResponseError, "WRONGTYPE"));
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(redis)), FailureKind::Storage);
+
+        let redis_timeout = redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        assert_eq!(FailureKind::classify(&anyhow::Error::from(redis_timeout)), FailureKind::Timeout);
+    }
+
+    #[test]
+    fn unrecognised_errors_are_internal() {
+        assert_eq!(FailureKind::classify(&anyhow!("index out of range")), FailureKind::Internal);
+    }
+}
@@ -1 +1,5 @@
+pub mod failure;
 pub mod node;
+pub mod runner;
+pub mod runs;
+pub mod state;
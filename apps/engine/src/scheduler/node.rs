@@ -1,7 +1,17 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use mongodb::bson::Document;
+
+/// Structured counts a task reports for its run (added/removed/unchanged, ...).
+pub type TaskSummary = Document;
+
+pub type TaskFuture = Pin<Box<dyn Future<Output = anyhow::Result<TaskSummary>> + Send>>;
+
 pub struct FetchNode {
     pub name: &'static str,
     pub interval: u64,
-    pub callback: fn() -> (),
+    pub callback: fn() -> TaskFuture,
 }
 
 inventory::collect!(FetchNode);
@@ -0,0 +1,95 @@
+use mongodb::bson::{oid::ObjectId, DateTime as BsonDateTime, Document};
+use chrono::{DateTime, Utc};
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::{field, info_span, Instrument, Span};
+
+use crate::alerting;
+use crate::logger::{log_error, log_event};
+use crate::scheduler::node::FetchNode;
+use crate::scheduler::runs::{record_run, TaskFailure, TaskRun};
+use crate::scheduler::state;
+
+/// Fire `node` every `interval` seconds forever. Runs are spawned, so a slow
+/// run never delays the next tick. Paused tasks keep ticking but skip the run;
+/// an interval change wakes the loop and reschedules from the last tick.
+pub async fn run_forever(node: &'static FetchNode) {
+    let wake = state::register(node);
+    loop {
+        if !state::is_paused(node.name) {
+            tokio::spawn(execute(node));
+        }
+
+        let fired_at = Instant::now();
+        loop {
+            let interval = Duration::from_secs(state::interval(node.name).unwrap_or(node.interval));
+            // The admin API caps intervals; never let a bad one panic the loop
+            let next = fired_at
+                .checked_add(interval)
+                .unwrap_or_else(|| fired_at + Duration::from_secs(state::MAX_INTERVAL_SECS));
+            let remaining = next.saturating_duration_since(Instant::now());
+            let next_fire_at = chrono::Duration::from_std(remaining)
+                .ok()
+                .and_then(|d| Utc::now().checked_add_signed(d))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            state::set_next_fire_at(node.name, next_fire_at);
+
+            tokio::select! {
+                _ = sleep_until(next) => break,
+                _ = wake.notified() => continue,
+            }
+        }
+    }
+}
+
+/// Run a node once, log its outcome and persist it to `engine_task_runs`.
+/// Each run is the root span of its own trace.
+pub async fn execute(node: &'static FetchNode) {
+    let id = ObjectId::new();
+    let span = info_span!(
+        "task.run",
+        task = node.name,
+        run_id = %id.to_hex(),
+        otel.status_code = field::Empty,
+        failure.kind = field::Empty,
+    );
+
+    async move {
+        let started = Instant::now();
+        let started_at = BsonDateTime::now();
+        log_event("TASK", &format!("running {} ({})", node.name, id.to_hex()), None);
+
+        let (summary, failure) = match (node.callback)().await {
+            Ok(summary) => (summary, None),
+            Err(e) => {
+                let failure = TaskFailure::from_error(&e);
+                let span = Span::current();
+                span.record("otel.status_code", "ERROR");
+                span.record("failure.kind", failure.kind.as_str());
+                log_error(
+                    &format!("{} failed [{}]", node.name, failure.kind),
+                    &e,
+                    Some(started.elapsed()),
+                );
+                (Document::new(), Some(failure))
+            }
+        };
+
+        let run = TaskRun {
+            id,
+            task: node.name,
+            started_at,
+            finished_at: BsonDateTime::now(),
+            failure,
+            summary,
+        };
+
+        if let Err(e) = record_run(&run).instrument(info_span!("mongo.record_run")).await {
+            log_error(&format!("recording run of {} failed", node.name), &e, None);
+        }
+
+        state::record_last_run(&run);
+        alerting::observe_run(node, &run).await;
+    }
+    .instrument(span)
+    .await
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, DateTime as BsonDateTime, Document},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+use tokio::sync::OnceCell;
+
+use crate::config::TASK_RUN_TTL_SECS;
+use crate::mongo_client::database;
+use crate::scheduler::failure::FailureKind;
+
+const RUNS_COLLECTION: &str = "engine_task_runs";
+
+static INDEXES_READY: OnceCell<()> = OnceCell::const_new();
+
+/// Why a run failed: its classification plus the rendered cause chain.
+pub struct TaskFailure {
+    pub kind: FailureKind,
+    pub chain: Vec<String>,
+}
+
+impl TaskFailure {
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        TaskFailure {
+            kind: FailureKind::classify(err),
+            chain: err.chain().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// The chain on one line, outermost context first.
+    pub fn message(&self) -> String {
+        self.chain.join(": ")
+    }
+}
+
+/// One scheduler run of a `FetchNode`, as stored in `engine_task_runs`.
+pub struct TaskRun {
+    pub id: ObjectId,
+    pub task: &'static str,
+    pub started_at: BsonDateTime,
+    pub finished_at: BsonDateTime,
+    pub failure: Option<TaskFailure>,
+    pub summary: Document,
+}
+
+impl TaskRun {
+    pub fn succeeded(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    fn to_document(&self) -> Document {
+        let duration_ms =
+            self.finished_at.timestamp_millis() - self.started_at.timestamp_millis();
+        let expire_at = BsonDateTime::from_millis(
+            self.finished_at.timestamp_millis() + *TASK_RUN_TTL_SECS * 1000,
+        );
+
+        doc! {
+            "_id": self.id,
+            "task": self.task,
+            "startedAt": self.started_at,
+            "finishedAt": self.finished_at,
+            "durationMs": duration_ms,
+            "outcome": if self.succeeded() { "success" } else { "failure" },
+            "error": self.failure.as_ref().map(|f| Bson::String(f.message())).unwrap_or(Bson::Null),
+            "errorChain": self.failure.as_ref().map(|f| f.chain.clone()).unwrap_or_default(),
+            "failureKind": self.failure.as_ref().map(|f| Bson::String(f.kind.to_string())).unwrap_or(Bson::Null),
+            "summary": self.summary.clone(),
+            "expireAt": expire_at,
+        }
+    }
+}
+
+async fn create_indexes(coll: &Collection<Document>) -> Result<()> {
+    // Old runs are removed by MongoDB once `expireAt` passes
+    let ttl_idx = IndexModel::builder()
+        .keys(doc! { "expireAt": 1 })
+        .options(IndexOptions::builder().expire_after(Some(Duration::from_secs(0))).build())
+        .build();
+    coll.create_index(ttl_idx, None)
+        .await
+        .context("creating engine_task_runs TTL index")?;
+
+    // Serves "latest run / latest success for task X" lookups
+    let task_idx = IndexModel::builder()
+        .keys(doc! { "task": 1, "outcome": 1, "startedAt": -1 })
+        .build();
+    coll.create_index(task_idx, None)
+        .await
+        .context("creating engine_task_runs task index")?;
+    Ok(())
+}
+
+/// Persist a finished run so "last successful update" can be read per task.
+/// Indexes are created by the first run of the process.
+pub async fn record_run(run: &TaskRun) -> Result<()> {
+    let db = database().await?;
+    let coll = db.collection::<Document>(RUNS_COLLECTION);
+    INDEXES_READY.get_or_try_init(|| create_indexes(&coll)).await?;
+
+    coll.insert_one(run.to_document(), None)
+        .await
+        .context("inserting engine_task_runs doc")?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::scheduler::node::FetchNode;
+use crate::scheduler::runs::TaskRun;
+
+/// Longest interval the admin API accepts (one day).
+pub const MAX_INTERVAL_SECS: u64 = 60 * 60 * 24;
+
+/// Outcome of the most recent finished run of a task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastRun {
+    pub run_id: String,
+    pub outcome: &'static str,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub failure_kind: Option<&'static str>,
+    pub error: Option<String>,
+}
+
+/// Runtime view of a `FetchNode`, as exposed by the admin API.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskStatus {
+    pub name: &'static str,
+    pub interval: u64,
+    pub default_interval: u64,
+    pub paused: bool,
+    pub next_fire_at: Option<DateTime<Utc>>,
+    pub last_run: Option<LastRun>,
+}
+
+/// Mutable scheduling state of a node. `wake` interrupts the node's sleep so
+/// interval changes apply without waiting out the old interval.
+struct TaskState {
+    node: &'static FetchNode,
+    interval: u64,
+    paused: bool,
+    next_fire_at: Option<DateTime<Utc>>,
+    last_run: Option<LastRun>,
+    wake: Arc<Notify>,
+}
+
+static STATE: Lazy<Mutex<HashMap<&'static str, TaskState>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Start tracking `node`; returns the handle its loop sleeps on.
+pub fn register(node: &'static FetchNode) -> Arc<Notify> {
+    let mut state = STATE.lock().unwrap();
+    let entry = state.entry(node.name).or_insert_with(|| TaskState {
+        node,
+        interval: node.interval,
+        paused: false,
+        next_fire_at: None,
+        last_run: None,
+        wake: Arc::new(Notify::new()),
+    });
+    entry.wake.clone()
+}
+
+pub fn node(name: &str) -> Option<&'static FetchNode> {
+    STATE.lock().unwrap().get(name).map(|s| s.node)
+}
+
+pub fn interval(name: &str) -> Option<u64> {
+    STATE.lock().unwrap().get(name).map(|s| s.interval)
+}
+
+pub fn is_paused(name: &str) -> bool {
+    STATE.lock().unwrap().get(name).is_some_and(|s| s.paused)
+}
+
+pub fn set_next_fire_at(name: &str, at: DateTime<Utc>) {
+    if let Some(s) = STATE.lock().unwrap().get_mut(name) {
+        s.next_fire_at = Some(at);
+    }
+}
+
+/// Pause or resume a task. Returns `false` for unknown tasks.
+pub fn set_paused(name: &str, paused: bool) -> bool {
+    match STATE.lock().unwrap().get_mut(name) {
+        Some(s) => {
+            s.paused = paused;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Change a task's interval and wake its loop to reschedule. Returns `false`
+/// for unknown tasks.
+pub fn set_interval(name: &str, interval: u64) -> bool {
+    match STATE.lock().unwrap().get_mut(name) {
+        Some(s) => {
+            s.interval = interval;
+            s.wake.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn record_last_run(run: &TaskRun) {
+    if let Some(s) = STATE.lock().unwrap().get_mut(run.task) {
+        s.last_run = Some(LastRun {
+            run_id: run.id.to_hex(),
+            outcome: if run.succeeded() { "success" } else { "failure" },
+            finished_at: Utc::now(),
+            duration_ms: run.finished_at.timestamp_millis() - run.started_at.timestamp_millis(),
+            failure_kind: run.failure.as_ref().map(|f| f.kind.as_str()),
+            error: run.failure.as_ref().map(|f| f.message()),
+        });
+    }
+}
+
+/// Every registered task, sorted by name.
+pub fn snapshot() -> Vec<TaskStatus> {
+    let state = STATE.lock().unwrap();
+    let mut tasks: Vec<TaskStatus> = state
+        .values()
+        .map(|s| TaskStatus {
+            name: s.node.name,
+            interval: s.interval,
+            default_interval: s.node.interval,
+            paused: s.paused,
+            next_fire_at: s.next_fire_at,
+            last_run: s.last_run.clone(),
+        })
+        .collect();
+    tasks.sort_by_key(|t| t.name);
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::node::TaskFuture;
+
+    fn noop() -> TaskFuture {
+        Box::pin(async { Ok(mongodb::bson::Document::new()) })
+    }
+
+    // The state is process-wide, so every test registers its own node
+    static PAUSE_NODE: FetchNode = FetchNode { name: "state_test_pause", interval: 30, callback: noop };
+    static INTERVAL_NODE: FetchNode = FetchNode { name: "state_test_interval", interval: 30, callback: noop };
+
+    #[test]
+    fn pause_and_resume_registered_tasks_only() {
+        register(&PAUSE_NODE);
+
+        assert!(!is_paused("state_test_pause"));
+        assert!(set_paused("state_test_pause", true));
+        assert!(is_paused("state_test_pause"));
+        assert!(set_paused("state_test_pause", false));
+        assert!(!is_paused("state_test_pause"));
+
+        assert!(!set_paused("state_test_unknown", true));
+        assert!(!is_paused("state_test_unknown"));
+    }
+
+    #[test]
+    fn interval_change_is_reported_beside_the_default() {
+        register(&INTERVAL_NODE);
+
+        assert!(set_interval("state_test_interval", 90));
+        assert_eq!(interval("state_test_interval"), Some(90));
+        let status = snapshot().into_iter().find(|t| t.name == "state_test_interval").unwrap();
+        assert_eq!((status.interval, status.default_interval), (90, 30));
+
+        assert!(!set_interval("state_test_unknown", 90));
+        assert_eq!(interval("state_test_unknown"), None);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    options::{FindOptions, IndexOptions},
+    Database, IndexModel,
+};
+use tokio::sync::OnceCell;
+use tracing::{info_span, Instrument};
+
+use crate::logger::log_event;
+use crate::mongo_client::{database, insert_ignoring_duplicates, run_write_command};
+use crate::scheduler::node::{TaskFuture, TaskSummary};
+use crate::tasks::world_events::schedule::SCHEDULES_COLLECTION;
+use wynnpool_engine_macros::fetch;
+
+pub const HISTORY_COLLECTION: &str = "world_event_history";
+const PREDICTIONS_COLLECTION: &str = "world_event_predictions";
+
+const ANNIHILATION_INTERNAL_NAME: &str = "Prelude to Annihilation";
+const FORECAST_HORIZON: usize = 10;
+// Occurrences needed before an event gets a forecast (one interval)
+const MIN_HISTORY: usize = 2;
+
+/// World event predictor.
+///
+/// For Annihilation the inter-event intervals are statistically
+/// indistinguishable from white noise (cleaned data: std ~0.13d on a ~3.19d
+/// mean, lag-1 autocorr ~0, Ljung-Box p > 0.3 at all lags). With no
+/// exploitable autocorrelation the historical mean is the optimal point
+/// forecast under squared-error loss, so this is a pure mean-reversion
+/// projector — no statistical crate needed. Every other scheduled event gets
+/// the same projector.
+///
+/// Each run: (1) append newly-observed event times of every event from the
+/// schedule snapshots, (2) recompute each event's forecast from its
+/// `world_event_history`, (3) upsert one prediction doc per event.
+#[fetch(interval = 300)]
+fn update_event_predictions() -> TaskFuture {
+    Box::pin(run_update_event_predictions())
+}
+
+/// Prediction doc `_id`: the internal name, except Annihilation which keeps
+/// the `"annihilation"` id the API has always read.
+fn prediction_id(internal_name: &str) -> &str {
+    if internal_name == ANNIHILATION_INTERNAL_NAME {
+        "annihilation"
+    } else {
+        internal_name
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub current_ts: i64,
+    pub current_predicted: bool,
+    pub predicted_ts: Vec<i64>,
+    pub mean_interval_ms: i64,
+}
+
+/// Project `FORECAST_HORIZON` occurrences from sorted history timestamps.
+/// A live schedule in the future is the `current` occurrence (observed);
+/// otherwise the first projection is. `None` below `MIN_HISTORY`. Pure.
+pub fn forecast(timestamps: &[i64], live_ts: Option<i64>, now_ms: i64) -> Option<Forecast> {
+    if timestamps.len() < MIN_HISTORY {
+        return None;
+    }
+
+    let intervals: Vec<i64> = timestamps.windows(2).map(|w| w[1] - w[0]).collect();
+    let mean_interval_ms = intervals.iter().sum::<i64>() / intervals.len() as i64;
+    let last_ts = *timestamps.last()?;
+
+    let predicted_ts: Vec<i64> = (1..=FORECAST_HORIZON as i64)
+        .map(|i| last_ts + mean_interval_ms * i)
+        .collect();
+
+    let (current_ts, current_predicted) = match live_ts {
+        Some(ts) if ts > now_ms => (ts, false),
+        _ => (predicted_ts[0], true),
+    };
+
+    Some(Forecast { current_ts, current_predicted, predicted_ts, mean_interval_ms })
+}
+
+static HISTORY_READY: OnceCell<()> = OnceCell::const_new();
+
+/// Run `migrate_history` once per process; it scans the whole collection.
+pub async fn ensure_history(db: &Database) -> Result<()> {
+    HISTORY_READY.get_or_try_init(|| migrate_history(db)).await?;
+    Ok(())
+}
+
+/// Per-event history used to live in one unnamed series keyed only by time.
+/// Tag those docs as Annihilation and move the unique index to (event, time).
+/// Entries from before `source` existed were all observed.
+async fn migrate_history(db: &Database) -> Result<()> {
+    let history_coll = db.collection::<Document>(HISTORY_COLLECTION);
+
+    history_coll
+        .update_many(
+            doc! { "internalName": { "$exists": false } },
+            doc! { "$set": { "internalName": ANNIHILATION_INTERNAL_NAME } },
+            None,
+        )
+        .instrument(info_span!("mongo.update_many", collection = HISTORY_COLLECTION))
+        .await
+        .context("tagging legacy world_event_history entries")?;
+    history_coll
+        .update_many(
+            doc! { "source": { "$exists": false } },
+            doc! { "$set": { "source": "observed" } },
+            None,
+        )
+        .instrument(info_span!("mongo.update_many", collection = HISTORY_COLLECTION))
+        .await
+        .context("tagging world_event_history sources")?;
+
+    // Only exists on databases from before per-event history; absence is fine
+    let _ = history_coll.drop_index("datetime_utc_1", None).await;
+
+    let idx = IndexModel::builder()
+        .keys(doc! { "internalName": 1, "datetime_utc": 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    history_coll
+        .create_index(idx, None)
+        .await
+        .context("creating world_event_history unique index")?;
+    Ok(())
+}
+
+async fn run_update_event_predictions() -> Result<TaskSummary> {
+    let whole_start = Instant::now();
+    log_event("TASK", "updating world event predictions", None);
+
+    // --- 1. MONGODB ---
+    let db = database().await?;
+    ensure_history(&db).await?;
+
+    let schedules_coll = db.collection::<Document>(SCHEDULES_COLLECTION);
+    let history_coll = db.collection::<Document>(HISTORY_COLLECTION);
+
+    let now_ms: i64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+
+    // --- 2. Detect + append newly-observed event times ---
+    // `world_event_schedules` holds one doc per distinct `schedule` value seen
+    // by the world_events task, with the parsed time in `scheduleAt`. A
+    // schedule that now lies in the past represents a completed event — record it.
+    let sched_filter = doc! {
+        "scheduleAt": { "$type": "date", "$lt": BsonDateTime::from_millis(now_ms) },
+    };
+    let sched_docs: Vec<Document> = async {
+        schedules_coll.find(sched_filter, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.find", collection = SCHEDULES_COLLECTION))
+    .await
+    .context("loading past schedule snapshots")?;
+
+    let observed: Vec<Document> = sched_docs
+        .iter()
+        .filter_map(|s| {
+            let name = s.get_str("internalName").ok()?;
+            let at = s.get_datetime("scheduleAt").ok()?;
+            Some(doc! { "internalName": name, "datetime_utc": at.timestamp_millis(), "source": "observed" })
+        })
+        .collect();
+    // Duplicates are the unique index doing its job: already-recorded occurrences
+    let appended_count = insert_ignoring_duplicates(&db, HISTORY_COLLECTION, observed)
+        .instrument(info_span!("mongo.append_history", collection = HISTORY_COLLECTION))
+        .await
+        .context("appending world event history")?;
+
+    // --- 3. Per-event history and latest live schedule ---
+    let hist_opts = FindOptions::builder()
+        .sort(Some(doc! { "internalName": 1, "datetime_utc": 1 }))
+        .build();
+    let hist_docs: Vec<Document> = async {
+        history_coll.find(None, hist_opts).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.find", collection = HISTORY_COLLECTION))
+    .await
+    .context("loading world_event_history")?;
+
+    let mut histories: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for hdoc in &hist_docs {
+        if let (Ok(name), Some(ts)) = (hdoc.get_str("internalName"), extract_i64(hdoc, "datetime_utc")) {
+            histories.entry(name.to_string()).or_default().push(ts);
+        }
+    }
+
+    let live_pipeline = vec![
+        doc! { "$match": { "scheduleAt": { "$type": "date" } } },
+        doc! { "$sort": { "lastObservedAt": -1 } },
+        doc! { "$group": { "_id": "$internalName", "scheduleAt": { "$first": "$scheduleAt" } } },
+    ];
+    let live_docs: Vec<Document> = async {
+        schedules_coll.aggregate(live_pipeline, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.aggregate", collection = SCHEDULES_COLLECTION))
+    .await
+    .context("loading latest live schedules")?;
+    let live: BTreeMap<String, i64> = live_docs
+        .iter()
+        .filter_map(|d| Some((d.get_str("_id").ok()?.to_string(), d.get_datetime("scheduleAt").ok()?.timestamp_millis())))
+        .collect();
+
+    // --- 4. Forecast every event with enough history ---
+    let now_iso = Utc::now().to_rfc3339();
+    let mut updates: Vec<Document> = Vec::new();
+    let mut skipped = 0usize;
+    for (name, timestamps) in &histories {
+        let Some(f) = forecast(timestamps, live.get(name).copied(), now_ms) else {
+            skipped += 1;
+            continue;
+        };
+
+        let predicted_arr: Vec<Bson> = f
+            .predicted_ts
+            .iter()
+            .map(|ts| {
+                Bson::Document(doc! {
+                    "datetime_utc": ts,
+                    "predicted": true,
+                })
+            })
+            .collect();
+
+        updates.push(doc! {
+            "q": { "_id": prediction_id(name) },
+            "u": { "$set": {
+                "internalName": name,
+                "current": {
+                    "datetime_utc": f.current_ts,
+                    "predicted": f.current_predicted,
+                },
+                "predicted": predicted_arr,
+                "meanIntervalMs": f.mean_interval_ms,
+                "historyCount": timestamps.len() as i64,
+                "updatedAt": now_iso.clone(),
+            }},
+            "upsert": true,
+        });
+    }
+
+    // --- 5. Upsert one prediction doc per event ---
+    let predicted_count = updates.len();
+    if !updates.is_empty() {
+        run_write_command(&db, doc! { "update": PREDICTIONS_COLLECTION, "updates": updates, "ordered": false })
+            .instrument(info_span!("mongo.bulk_update", collection = PREDICTIONS_COLLECTION, count = predicted_count))
+            .await
+            .context("upserting world event predictions")?;
+    }
+
+    let whole_elapsed = whole_start.elapsed();
+    log_event(
+        "SUMMARY",
+        &format!(
+            "predictions: events={}, predicted={}, history too small={}, appended={}, forecast={}",
+            histories.len(),
+            predicted_count,
+            skipped,
+            appended_count,
+            FORECAST_HORIZON,
+        ),
+        Some(whole_elapsed),
+    );
+
+    Ok(doc! {
+        "events": histories.len() as i64,
+        "predicted": predicted_count as i64,
+        "skipped": skipped as i64,
+        "appended": appended_count as i64,
+    })
+}
+
+/// Tolerantly extract an i64 from any numeric-ish BSON field.
+pub fn extract_i64(doc: &Document, field: &str) -> Option<i64> {
+    match doc.get(field)? {
+        Bson::Int64(n) => Some(*n),
+        Bson::Int32(n) => Some(*n as i64),
+        Bson::Double(n) => Some(*n as i64),
+        Bson::DateTime(dt) => Some(dt.timestamp_millis()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_MS: i64 = 1_700_000_000_000;
+    const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+    #[test]
+    fn projects_mean_interval_from_last_occurrence() {
+        let history = [NOW_MS - 7 * DAY_MS, NOW_MS - 4 * DAY_MS, NOW_MS - DAY_MS];
+        let f = forecast(&history, None, NOW_MS).unwrap();
+
+        assert_eq!(f.mean_interval_ms, 3 * DAY_MS);
+        assert_eq!(f.predicted_ts.len(), FORECAST_HORIZON);
+        assert_eq!(f.predicted_ts[0], NOW_MS + 2 * DAY_MS);
+        assert_eq!((f.current_ts, f.current_predicted), (NOW_MS + 2 * DAY_MS, true));
+    }
+
+    #[test]
+    fn future_live_schedule_is_current() {
+        let history = [NOW_MS - 4 * DAY_MS, NOW_MS - DAY_MS];
+
+        let f = forecast(&history, Some(NOW_MS + DAY_MS), NOW_MS).unwrap();
+        assert_eq!((f.current_ts, f.current_predicted), (NOW_MS + DAY_MS, false));
+
+        let stale = forecast(&history, Some(NOW_MS - DAY_MS), NOW_MS).unwrap();
+        assert!(stale.current_predicted);
+    }
+
+    #[test]
+    fn too_little_history_has_no_forecast() {
+        assert_eq!(forecast(&[NOW_MS], None, NOW_MS), None);
+    }
+
+    #[test]
+    fn annihilation_keeps_its_prediction_id() {
+        assert_eq!(prediction_id(ANNIHILATION_INTERNAL_NAME), "annihilation");
+        assert_eq!(prediction_id("Corrupted Spire"), "Corrupted Spire");
+    }
+}
@@ -1,3 +1,6 @@
-pub mod annihilation;
+pub mod event_predictions;
+pub mod player_counts;
 pub mod server_status;
+pub mod sessions;
+pub mod watchlist;
 pub mod world_events;
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    options::{CreateCollectionOptions, FindOneOptions, TimeseriesGranularity, TimeseriesOptions},
+    Database,
+};
+use tokio::sync::OnceCell;
+use tracing::{info_span, Instrument};
+
+use crate::config::{
+    PLAYER_COUNT_BUCKET_SECS, PLAYER_COUNT_DOWNSAMPLED_RETENTION_SECS,
+    PLAYER_COUNT_RAW_RETENTION_SECS,
+};
+use crate::logger::log_event;
+use crate::mongo_client::database;
+use crate::scheduler::node::{TaskFuture, TaskSummary};
+use wynnpool_engine_macros::fetch;
+
+/// One point per server per server_status tick.
+pub const RAW_COLLECTION: &str = "server_player_counts";
+/// One point per server per `PLAYER_COUNT_BUCKET_SECS` bucket.
+pub const DOWNSAMPLED_COLLECTION: &str = "server_player_counts_downsampled";
+
+// Points carry the time their server_status tick started; a slow tick inserts
+// them up to this long after, so a bucket is only closed once it has passed
+const LATE_POINT_GRACE_MS: i64 = 2 * 60 * 1000;
+
+static COLLECTIONS_READY: OnceCell<()> = OnceCell::const_new();
+
+/// Create both time-series collections if they do not exist yet, or apply the
+/// configured retention to them if they do, once per process. Retention is
+/// MongoDB's own `expireAfterSeconds`, so nothing here ever deletes points.
+pub async fn ensure_collections(db: &Database) -> Result<()> {
+    COLLECTIONS_READY.get_or_try_init(|| create_collections(db)).await?;
+    Ok(())
+}
+
+async fn create_collections(db: &Database) -> Result<()> {
+    let existing = db
+        .list_collection_names(None)
+        .await
+        .context("listing collections")?;
+
+    let specs = [
+        (RAW_COLLECTION, TimeseriesGranularity::Seconds, *PLAYER_COUNT_RAW_RETENTION_SECS),
+        (DOWNSAMPLED_COLLECTION, TimeseriesGranularity::Minutes, *PLAYER_COUNT_DOWNSAMPLED_RETENTION_SECS),
+    ];
+
+    for (name, granularity, retention_secs) in specs {
+        if existing.iter().any(|c| c == name) {
+            // Retention may have changed since the collection was created
+            db.run_command(doc! { "collMod": name, "expireAfterSeconds": retention_secs as i64 }, None)
+                .await
+                .with_context(|| format!("setting retention of {name}"))?;
+            continue;
+        }
+        let opts = CreateCollectionOptions::builder()
+            .timeseries(
+                TimeseriesOptions::builder()
+                    .time_field("ts".to_string())
+                    .meta_field(Some("server".to_string()))
+                    .granularity(Some(granularity))
+                    .build(),
+            )
+            .expire_after_seconds(Duration::from_secs(retention_secs))
+            .build();
+        db.create_collection(name, opts)
+            .await
+            .with_context(|| format!("creating time-series collection {name}"))?;
+        log_event("SETUP", &format!("created time-series collection {name}"), None);
+    }
+
+    Ok(())
+}
+
+/// Append one raw player count point per server in the snapshot.
+pub async fn record_player_counts(
+    db: &Database,
+    servers: &HashMap<String, Vec<String>>,
+    at: BsonDateTime,
+) -> Result<usize> {
+    if servers.is_empty() {
+        return Ok(0);
+    }
+    ensure_collections(db).await?;
+
+    let points: Vec<Document> = servers
+        .iter()
+        .map(|(server, players)| {
+            doc! {
+                "ts": at,
+                "server": server.clone(),
+                "playerCount": players.len() as i64,
+            }
+        })
+        .collect();
+    let count = points.len();
+
+    db.collection::<Document>(RAW_COLLECTION)
+        .insert_many(points, None)
+        .instrument(info_span!("mongo.insert_many", collection = RAW_COLLECTION, count))
+        .await
+        .context("inserting player count points")?;
+
+    Ok(count)
+}
+
+/// The `[start, end)` range of buckets to aggregate, or `None` when no bucket
+/// is ready.
+///
+/// `start` follows the newest downsampled bucket (or the oldest raw point
+/// still retained on the first run). `end` is the last bucket boundary at
+/// least `LATE_POINT_GRACE_MS` in the past, so points inserted late still
+/// land in a bucket that has not been written yet. Pure.
+pub fn downsample_window(
+    latest_bucket_ms: Option<i64>,
+    now_ms: i64,
+    bucket_ms: i64,
+    raw_retention_ms: i64,
+) -> Option<(i64, i64)> {
+    let settled = now_ms - LATE_POINT_GRACE_MS;
+    let end_ms = settled - settled.rem_euclid(bucket_ms);
+    let start_ms = match latest_bucket_ms {
+        Some(bucket) => bucket + bucket_ms,
+        None => {
+            let oldest_raw = now_ms - raw_retention_ms;
+            oldest_raw - oldest_raw.rem_euclid(bucket_ms)
+        }
+    };
+    (start_ms < end_ms).then_some((start_ms, end_ms))
+}
+
+/// Roll completed raw buckets up into per-server averages.
+///
+/// Progress is the newest bucket already in the downsampled collection, so a
+/// run only ever aggregates buckets that are both complete and not yet written.
+/// Time-series collections cannot carry unique indexes; this watermark is what
+/// keeps buckets from being written twice.
+#[fetch(interval = 600)]
+fn downsample_player_counts() -> TaskFuture {
+    Box::pin(run_downsample_player_counts())
+}
+
+async fn run_downsample_player_counts() -> Result<TaskSummary> {
+    let whole_start = Instant::now();
+    log_event("TASK", "downsampling player counts", None);
+
+    let db = database().await?;
+    ensure_collections(&db).await?;
+
+    let raw_coll = db.collection::<Document>(RAW_COLLECTION);
+    let down_coll = db.collection::<Document>(DOWNSAMPLED_COLLECTION);
+
+    let bucket_ms = *PLAYER_COUNT_BUCKET_SECS * 1000;
+    let now_ms = BsonDateTime::now().timestamp_millis();
+
+    let latest = down_coll
+        .find_one(None, FindOneOptions::builder().sort(doc! { "ts": -1 }).build())
+        .instrument(info_span!("mongo.find_one", collection = DOWNSAMPLED_COLLECTION))
+        .await
+        .context("loading downsample watermark")?;
+    let latest_bucket_ms = latest
+        .as_ref()
+        .and_then(|d| d.get_datetime("ts").ok())
+        .map(|ts| ts.timestamp_millis());
+    let window = downsample_window(
+        latest_bucket_ms,
+        now_ms,
+        bucket_ms,
+        *PLAYER_COUNT_RAW_RETENTION_SECS as i64 * 1000,
+    );
+
+    let Some((start_ms, end_ms)) = window else {
+        log_event(
+            "SUMMARY",
+            "player counts: no complete bucket to downsample",
+            Some(whole_start.elapsed()),
+        );
+        return Ok(doc! { "buckets": 0i64, "points": 0i64 });
+    };
+
+    let pipeline = vec![
+        doc! { "$match": {
+            "ts": {
+                "$gte": BsonDateTime::from_millis(start_ms),
+                "$lt": BsonDateTime::from_millis(end_ms),
+            },
+        }},
+        doc! { "$group": {
+            "_id": {
+                "server": "$server",
+                "bucket": { "$subtract": ["$ts", { "$mod": [{ "$toLong": "$ts" }, bucket_ms] }] },
+            },
+            "avgPlayers": { "$avg": "$playerCount" },
+            "minPlayers": { "$min": "$playerCount" },
+            "maxPlayers": { "$max": "$playerCount" },
+            "samples": { "$sum": 1 },
+        }},
+        doc! { "$sort": { "_id.bucket": 1, "_id.server": 1 } },
+    ];
+
+    let groups: Vec<Document> = async {
+        raw_coll.aggregate(pipeline, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.aggregate", collection = RAW_COLLECTION))
+    .await
+    .context("aggregating raw player counts")?;
+
+    let mut buckets: Vec<i64> = Vec::new();
+    let points: Vec<Document> = groups
+        .into_iter()
+        .filter_map(|g| {
+            let id = g.get_document("_id").ok()?;
+            let bucket = id.get_datetime("bucket").ok()?;
+            if buckets.last() != Some(&bucket.timestamp_millis()) {
+                buckets.push(bucket.timestamp_millis());
+            }
+            Some(doc! {
+                "ts": *bucket,
+                "server": id.get("server").cloned().unwrap_or(Bson::Null),
+                "avgPlayers": g.get_f64("avgPlayers").unwrap_or(0.0),
+                "minPlayers": g.get("minPlayers").cloned().unwrap_or(Bson::Null),
+                "maxPlayers": g.get("maxPlayers").cloned().unwrap_or(Bson::Null),
+                "samples": g.get("samples").cloned().unwrap_or(Bson::Null),
+            })
+        })
+        .collect();
+    let point_count = points.len();
+
+    if !points.is_empty() {
+        down_coll
+            .insert_many(points, None)
+            .instrument(info_span!("mongo.insert_many", collection = DOWNSAMPLED_COLLECTION, count = point_count))
+            .await
+            .context("inserting downsampled player counts")?;
+    }
+
+    log_event(
+        "SUMMARY",
+        &format!(
+            "player counts: downsampled buckets={} points={} window=[{}, {})",
+            buckets.len(),
+            point_count,
+            BsonDateTime::from_millis(start_ms),
+            BsonDateTime::from_millis(end_ms),
+        ),
+        Some(whole_start.elapsed()),
+    );
+
+    Ok(doc! {
+        "buckets": buckets.len() as i64,
+        "points": point_count as i64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUCKET_MS: i64 = 10 * 60 * 1000;
+    const RETENTION_MS: i64 = 48 * 60 * 60 * 1000;
+    // A bucket boundary
+    const T: i64 = 1_700_000_400_000;
+
+    #[test]
+    fn first_run_starts_at_oldest_retained_bucket() {
+        let (start, end) = downsample_window(None, T + BUCKET_MS, BUCKET_MS, RETENTION_MS).unwrap();
+
+        assert_eq!(start, T + BUCKET_MS - RETENTION_MS);
+        assert_eq!(end, T);
+    }
+
+    #[test]
+    fn continues_after_latest_bucket() {
+        let window = downsample_window(Some(T - 3 * BUCKET_MS), T + BUCKET_MS, BUCKET_MS, RETENTION_MS);
+        assert_eq!(window, Some((T - 2 * BUCKET_MS, T)));
+
+        assert_eq!(downsample_window(Some(T - BUCKET_MS), T + BUCKET_MS, BUCKET_MS, RETENTION_MS), None);
+    }
+
+    #[test]
+    fn late_point_lands_in_an_open_bucket() {
+        // A tick starting just before T stamps its points T - 1s and inserts
+        // them a minute later
+        let point_ts = T - 1000;
+        let inserted_at = T + 60 * 1000;
+
+        // A run between the boundary and the insert leaves that bucket open
+        let early = downsample_window(Some(T - 2 * BUCKET_MS), T + 30 * 1000, BUCKET_MS, RETENTION_MS);
+        assert_eq!(early, None);
+
+        // Once the grace has passed the bucket is aggregated, late point included
+        let (start, end) = downsample_window(Some(T - 2 * BUCKET_MS), T + LATE_POINT_GRACE_MS, BUCKET_MS, RETENTION_MS)
+            .unwrap();
+        assert!(inserted_at <= T + LATE_POINT_GRACE_MS);
+        assert!((start..end).contains(&point_ts));
+    }
+}
@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    options::{IndexOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
+use tokio::sync::OnceCell;
+use tracing::{info_span, Instrument};
+
+use super::population::PopulationSample;
+use super::region::is_regular_region;
+use crate::config::{ANOMALY_DROP_PERCENT, ANOMALY_MIN_PLAYERS, ANOMALY_STABLE_POLLS, ANOMALY_TTL_SECS};
+
+/// Last accepted snapshot and suspect-mode progress, one doc (`_id` = "server_status").
+const GUARD_COLLECTION: &str = "server_status_guard";
+/// Every anomalous snapshot, for later inspection; expires after `ANOMALY_TTL_SECS`.
+const ANOMALY_COLLECTION: &str = "server_status_anomalies";
+
+static TTL_INDEX_READY: OnceCell<()> = OnceCell::const_new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalySettings {
+    /// A drop in total players larger than this share of the baseline is suspect.
+    pub drop_fraction: f64,
+    /// Baselines smaller than this are too noisy to judge.
+    pub min_players: i64,
+    /// Consecutive suspect polls after which the new numbers are accepted as real.
+    pub stable_polls: i64,
+}
+
+impl AnomalySettings {
+    pub fn from_config() -> Self {
+        AnomalySettings {
+            drop_fraction: *ANOMALY_DROP_PERCENT / 100.0,
+            min_players: *ANOMALY_MIN_PLAYERS,
+            stable_polls: (*ANOMALY_STABLE_POLLS).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anomaly {
+    SuddenDrop { from: i64, to: i64 },
+    RegionVanished { region: String, players: i64 },
+}
+
+impl Anomaly {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Anomaly::SuddenDrop { .. } => "sudden_drop",
+            Anomaly::RegionVanished { .. } => "region_vanished",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Anomaly::SuddenDrop { from, to } => format!("players dropped from {from} to {to}"),
+            Anomaly::RegionVanished { region, players } => {
+                format!("region {region} vanished (had {players} players)")
+            }
+        }
+    }
+}
+
+/// Persisted guard state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuardState {
+    pub baseline: Option<PopulationSample>,
+    pub suspect_polls: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assessment {
+    pub anomalies: Vec<Anomaly>,
+    /// The snapshot may be incomplete; see `write_plan` for what it may touch.
+    pub suspect: bool,
+    pub next: GuardState,
+}
+
+/// Which of a tick's writes may use the snapshot. Upserts of the servers it
+/// does list are always applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WritePlan {
+    /// Offline marks and deletes of servers missing from the snapshot.
+    pub destructive: bool,
+    /// Region totals, population stats and player count points.
+    pub history: bool,
+    /// Session closes and presence events for players missing from it.
+    pub sessions: bool,
+    /// The Redis status cache and its change notification.
+    pub publish: bool,
+}
+
+impl Assessment {
+    /// A suspect snapshot may be missing whole servers, so everything that
+    /// would record or announce their absence waits for a trusted one.
+    pub fn write_plan(&self) -> WritePlan {
+        let trusted = !self.suspect;
+        WritePlan { destructive: trusted, history: trusted, sessions: trusted, publish: trusted }
+    }
+}
+
+/// Compare a snapshot against the last accepted one.
+///
+/// Any anomaly puts the task in suspect mode and keeps the old baseline. After
+/// `stable_polls` suspect polls in a row the snapshot is accepted anyway (a
+/// real outage looks the same as a bad response, only longer). A clean
+/// snapshot leaves suspect mode immediately. Pure.
+pub fn assess(current: &PopulationSample, guard: &GuardState, settings: &AnomalySettings) -> Assessment {
+    let mut anomalies = Vec::new();
+
+    if let Some(baseline) = &guard.baseline {
+        if baseline.online >= settings.min_players
+            && (current.online as f64) < baseline.online as f64 * (1.0 - settings.drop_fraction)
+        {
+            anomalies.push(Anomaly::SuddenDrop { from: baseline.online, to: current.online });
+        }
+        // Special worlds come and go on their own; only regular regions vanishing is suspicious
+        for (region, players) in &baseline.regions {
+            if *players > 0 && is_regular_region(region) && !current.regions.contains_key(region) {
+                anomalies.push(Anomaly::RegionVanished { region: region.clone(), players: *players });
+            }
+        }
+    }
+
+    let suspect_polls = if anomalies.is_empty() { 0 } else { guard.suspect_polls + 1 };
+    let suspect = suspect_polls > 0 && suspect_polls < settings.stable_polls;
+
+    let next = if suspect {
+        GuardState { baseline: guard.baseline.clone(), suspect_polls }
+    } else {
+        GuardState { baseline: Some(current.clone()), suspect_polls: 0 }
+    };
+
+    Assessment { anomalies, suspect, next }
+}
+
+pub async fn load_guard(db: &Database) -> Result<GuardState> {
+    let found = db
+        .collection::<Document>(GUARD_COLLECTION)
+        .find_one(doc! { "_id": "server_status" }, None)
+        .instrument(info_span!("mongo.find_one", collection = GUARD_COLLECTION))
+        .await
+        .context("loading server status guard")?;
+    let Some(d) = found else {
+        return Ok(GuardState::default());
+    };
+
+    let baseline = d.get_document("baseline").ok().map(|b| PopulationSample {
+        online: b.get_i64("online").unwrap_or(0),
+        regions: b
+            .get_document("regions")
+            .map(|r| r.iter().filter_map(|(k, v)| v.as_i64().map(|n| (k.clone(), n))).collect())
+            .unwrap_or_default(),
+    });
+    Ok(GuardState {
+        baseline,
+        suspect_polls: d.get_i64("suspectPolls").unwrap_or(0),
+    })
+}
+
+async fn create_ttl_index(coll: &Collection<Document>) -> Result<()> {
+    let idx = IndexModel::builder()
+        .keys(doc! { "expireAt": 1 })
+        .options(IndexOptions::builder().expire_after(Some(Duration::from_secs(0))).build())
+        .build();
+    coll.create_index(idx, None)
+        .await
+        .context("creating server_status_anomalies TTL index")?;
+    Ok(())
+}
+
+/// Persist the next guard state and record any anomalies.
+pub async fn record_assessment(
+    db: &Database,
+    assessment: &Assessment,
+    current: &PopulationSample,
+    now_ts: i64,
+) -> Result<()> {
+    let at = BsonDateTime::from_millis(now_ts * 1000);
+    let baseline = match &assessment.next.baseline {
+        Some(b) => {
+            let regions: Document = b.regions.iter().map(|(k, v)| (k.clone(), Bson::Int64(*v))).collect();
+            Bson::Document(doc! { "online": b.online, "regions": regions })
+        }
+        None => Bson::Null,
+    };
+
+    db.collection::<Document>(GUARD_COLLECTION)
+        .update_one(
+            doc! { "_id": "server_status" },
+            doc! { "$set": {
+                "baseline": baseline,
+                "suspectPolls": assessment.next.suspect_polls,
+                "suspect": assessment.suspect,
+                "updatedAt": at,
+            }},
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .instrument(info_span!("mongo.update_one", collection = GUARD_COLLECTION))
+        .await
+        .context("saving server status guard")?;
+
+    if assessment.anomalies.is_empty() {
+        return Ok(());
+    }
+    let expire_at = BsonDateTime::from_millis((now_ts + *ANOMALY_TTL_SECS) * 1000);
+    let docs: Vec<Document> = assessment
+        .anomalies
+        .iter()
+        .map(|a| {
+            doc! {
+                "at": at,
+                "kind": a.kind(),
+                "detail": a.describe(),
+                "online": current.online,
+                "suspect": assessment.suspect,
+                "expireAt": expire_at,
+            }
+        })
+        .collect();
+    let count = docs.len();
+    let coll = db.collection::<Document>(ANOMALY_COLLECTION);
+    TTL_INDEX_READY.get_or_try_init(|| create_ttl_index(&coll)).await?;
+    coll.insert_many(docs, None)
+        .instrument(info_span!("mongo.insert_many", collection = ANOMALY_COLLECTION, count))
+        .await
+        .context("recording server status anomalies")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    const SETTINGS: AnomalySettings = AnomalySettings {
+        drop_fraction: 0.5,
+        min_players: 100,
+        stable_polls: 3,
+    };
+
+    fn sample(regions: &[(&str, i64)]) -> PopulationSample {
+        let regions: BTreeMap<String, i64> = regions.iter().map(|(r, n)| (r.to_string(), *n)).collect();
+        PopulationSample { online: regions.values().sum(), regions }
+    }
+
+    fn guard(regions: &[(&str, i64)], suspect_polls: i64) -> GuardState {
+        GuardState { baseline: Some(sample(regions)), suspect_polls }
+    }
+
+    #[test]
+    fn first_snapshot_becomes_baseline() {
+        let current = sample(&[("EU", 500)]);
+        let assessment = assess(&current, &GuardState::default(), &SETTINGS);
+
+        assert!(!assessment.suspect);
+        assert_eq!(assessment.next.baseline, Some(current));
+    }
+
+    #[test]
+    fn vanished_region_is_suspect_and_keeps_baseline() {
+        let assessment = assess(&sample(&[("EU", 500)]), &guard(&[("EU", 500), ("NA", 300)], 0), &SETTINGS);
+
+        assert!(assessment.suspect);
+        assert_eq!(
+            assessment.anomalies,
+            vec![Anomaly::RegionVanished { region: "NA".into(), players: 300 }]
+        );
+        assert_eq!(assessment.next, guard(&[("EU", 500), ("NA", 300)], 1));
+    }
+
+    #[test]
+    fn special_worlds_vanishing_is_not_an_anomaly() {
+        let current = sample(&[("EU", 500)]);
+        let assessment = assess(&current, &guard(&[("EU", 500), ("youtube", 20), ("beta", 5)], 0), &SETTINGS);
+
+        assert!(assessment.anomalies.is_empty());
+        assert!(!assessment.suspect);
+    }
+
+    #[test]
+    fn suspect_snapshot_only_upserts_listed_servers() {
+        let suspect = assess(&sample(&[("EU", 500)]), &guard(&[("EU", 500), ("NA", 300)], 0), &SETTINGS);
+        assert_eq!(
+            suspect.write_plan(),
+            WritePlan { destructive: false, history: false, sessions: false, publish: false }
+        );
+
+        let clean = assess(&sample(&[("EU", 500), ("NA", 300)]), &guard(&[("EU", 500), ("NA", 300)], 0), &SETTINGS);
+        assert_eq!(
+            clean.write_plan(),
+            WritePlan { destructive: true, history: true, sessions: true, publish: true }
+        );
+    }
+
+    #[test]
+    fn sudden_drop_is_suspect_only_above_min_players() {
+        let assessment = assess(&sample(&[("EU", 100)]), &guard(&[("EU", 500)], 0), &SETTINGS);
+        assert_eq!(assessment.anomalies, vec![Anomaly::SuddenDrop { from: 500, to: 100 }]);
+        assert!(assessment.suspect);
+
+        let quiet = assess(&sample(&[("EU", 10)]), &guard(&[("EU", 50)], 0), &SETTINGS);
+        assert!(quiet.anomalies.is_empty());
+        assert!(!quiet.suspect);
+    }
+
+    #[test]
+    fn persistent_anomaly_is_accepted_after_stable_polls() {
+        let current = sample(&[("EU", 500)]);
+        let assessment = assess(&current, &guard(&[("EU", 500), ("NA", 300)], 2), &SETTINGS);
+
+        assert!(!assessment.suspect);
+        assert_eq!(assessment.next, GuardState { baseline: Some(current), suspect_polls: 0 });
+    }
+}
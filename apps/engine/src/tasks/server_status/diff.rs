@@ -0,0 +1,586 @@
+use std::collections::{BTreeMap, HashMap};
+
+use mongodb::bson::{doc, Bson, DateTime as BsonDateTime, Document};
+
+use super::region::parse_server_name;
+use crate::config::{OFFLINE_DELETE_SECS, SERVER_DATA_TTL_SECS, SERVER_OFFLINE_AFTER_POLLS};
+
+/// Tunables of the reconciliation, split out so tests can pin them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncSettings {
+    /// Consecutive polls a server must be absent before it is marked offline.
+    pub offline_after_polls: i64,
+    /// Seconds a server may stay offline before its doc is deleted.
+    pub offline_delete_secs: i64,
+    /// Seconds until an untouched server doc expires.
+    pub data_ttl_secs: i64,
+}
+
+impl SyncSettings {
+    pub fn from_config() -> Self {
+        SyncSettings {
+            offline_after_polls: (*SERVER_OFFLINE_AFTER_POLLS).max(1),
+            offline_delete_secs: *OFFLINE_DELETE_SECS,
+            data_ttl_secs: *SERVER_DATA_TTL_SECS,
+        }
+    }
+}
+
+/// The parts of a stored `wynncraft_servers` doc the reconciliation needs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoredServer {
+    pub first_seen: Option<i64>,
+    pub offline_since: Option<i64>,
+    /// Consecutive polls the server has been absent from the API.
+    pub missed_polls: i64,
+    /// When the current absence started.
+    pub missing_since: Option<i64>,
+    /// Players stored with the doc, still reported while the server is missing.
+    pub players: Vec<String>,
+}
+
+impl StoredServer {
+    pub fn from_doc(doc: &Document) -> Self {
+        StoredServer {
+            first_seen: doc.get_i64("firstSeen").ok(),
+            offline_since: doc.get_i64("offlineSince").ok(),
+            missed_polls: doc.get_i64("missedPolls").unwrap_or(0),
+            missing_since: doc.get_i64("missingSince").ok(),
+            players: doc
+                .get_array("players")
+                .map(|ps| ps.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// One write the reconciliation wants applied to `wynncraft_servers`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerWrite {
+    /// Upsert a server present in the API with its current players.
+    Online {
+        server: String,
+        players: Vec<String>,
+        first_seen: i64,
+    },
+    /// Absent from the API, but not for enough polls to be called offline yet.
+    /// The doc keeps its online state and players.
+    Missing {
+        server: String,
+        missed_polls: i64,
+        missing_since: i64,
+    },
+    /// Keep a server in the DB but mark it offline.
+    Offline {
+        server: String,
+        first_seen: i64,
+        offline_since: i64,
+    },
+    /// Remove a server offline for longer than the offline grace.
+    Delete {
+        server: String,
+        first_seen: i64,
+        offline_since: i64,
+    },
+}
+
+/// Result of comparing the API snapshot against the stored servers.
+#[derive(Debug, Default, PartialEq)]
+pub struct ServerDiff {
+    /// Writes for every stored and listed server, missing ones first, then
+    /// upserts. `apply_server_writes` applies deletes after all the others.
+    pub writes: Vec<ServerWrite>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl ServerDiff {
+    /// Drop every write that would take a server offline or delete it, for
+    /// snapshots that cannot be trusted to be complete.
+    pub fn without_destructive(mut self) -> Self {
+        self.writes.retain(|w| matches!(w, ServerWrite::Online { .. }));
+        self.unchanged += self.removed.len();
+        self.removed.clear();
+        self
+    }
+
+    /// Servers that remain online after the writes, sorted.
+    pub fn online(&self) -> Vec<String> {
+        let mut online: Vec<String> = self
+            .writes
+            .iter()
+            .filter_map(|w| match w {
+                ServerWrite::Online { server, players, .. } if !players.is_empty() => Some(server.clone()),
+                ServerWrite::Missing { server, .. } => Some(server.clone()),
+                _ => None,
+            })
+            .collect();
+        online.sort();
+        online
+    }
+
+    /// Servers that remain in the DB but offline after the writes, sorted.
+    pub fn offline(&self) -> Vec<String> {
+        let mut offline: Vec<String> = self
+            .writes
+            .iter()
+            .filter_map(|w| match w {
+                ServerWrite::Offline { server, .. } => Some(server.clone()),
+                ServerWrite::Online { server, players, .. } if players.is_empty() => Some(server.clone()),
+                _ => None,
+            })
+            .collect();
+        offline.sort();
+        offline
+    }
+}
+
+/// Reconcile the current server → players map against the stored docs.
+///
+/// A server missing from the API first counts missed polls; only after
+/// `offline_after_polls` in a row is it marked offline (as of when it first
+/// went missing), and only after `offline_delete_secs` offline is it deleted.
+/// Reappearing at any point before deletion keeps `firstSeen`, so uptime
+/// survives short gaps.
+///
+/// Pure: no I/O and no clock, so fixtures fully determine the output. Servers
+/// are visited in name order to keep the write order stable.
+pub fn diff_servers(
+    current: &HashMap<String, Vec<String>>,
+    existing: &HashMap<String, StoredServer>,
+    now_ts: i64,
+    settings: &SyncSettings,
+) -> ServerDiff {
+    let current: BTreeMap<&String, &Vec<String>> = current.iter().collect();
+    let existing: BTreeMap<&String, &StoredServer> = existing.iter().collect();
+    let mut diff = ServerDiff::default();
+
+    // Existing servers that are *missing* from current API → missing, offline or delete
+    for (server, stored) in &existing {
+        if current.contains_key(server) {
+            continue;
+        }
+        let first_seen = stored.first_seen.unwrap_or(now_ts);
+
+        if let Some(offline_since) = stored.offline_since {
+            if now_ts - offline_since > settings.offline_delete_secs {
+                diff.writes.push(ServerWrite::Delete {
+                    server: (*server).clone(),
+                    first_seen,
+                    offline_since,
+                });
+                diff.removed.push((*server).clone());
+            } else {
+                diff.unchanged += 1;
+                diff.writes.push(ServerWrite::Offline {
+                    server: (*server).clone(),
+                    first_seen,
+                    offline_since,
+                });
+            }
+            continue;
+        }
+
+        diff.unchanged += 1;
+        let missed_polls = stored.missed_polls + 1;
+        let missing_since = stored.missing_since.unwrap_or(now_ts);
+        if missed_polls >= settings.offline_after_polls {
+            diff.writes.push(ServerWrite::Offline {
+                server: (*server).clone(),
+                first_seen,
+                offline_since: missing_since,
+            });
+        } else {
+            diff.writes.push(ServerWrite::Missing {
+                server: (*server).clone(),
+                missed_polls,
+                missing_since,
+            });
+        }
+    }
+
+    // Servers that are present in current API → online
+    for (server, players) in &current {
+        let stored = existing.get(server);
+        let first_seen = stored.and_then(|s| s.first_seen).unwrap_or(now_ts);
+
+        diff.writes.push(ServerWrite::Online {
+            server: (*server).clone(),
+            players: (*players).clone(),
+            first_seen,
+        });
+
+        if stored.is_none() {
+            diff.added.push((*server).clone());
+        } else {
+            diff.unchanged += 1;
+        }
+    }
+
+    diff
+}
+
+/// Render a non-delete write as an `update` command statement.
+pub fn update_statement(write: &ServerWrite, now_ts: i64, settings: &SyncSettings) -> Option<Document> {
+    let expire_at = BsonDateTime::from_millis((now_ts + settings.data_ttl_secs) * 1000);
+
+    let (server, mut set, unset) = match write {
+        ServerWrite::Online { server, players, first_seen } if !players.is_empty() => (
+            server,
+            doc! {
+                "server": server.clone(),
+                "online": true,
+                "playerCount": players.len() as i64,
+                "players": players.iter().cloned().map(Bson::String).collect::<Vec<_>>(),
+                "firstSeen": *first_seen,
+                "expireAt": expire_at,
+            },
+            // Back online: clear the offline markers so the next outage starts fresh
+            Some(doc! { "offlineSince": "", "missedPolls": "", "missingSince": "" }),
+        ),
+        // Present in the API with zero players: treat as offline since now
+        ServerWrite::Online { server, first_seen, .. } => (
+            server,
+            doc! {
+                "server": server.clone(),
+                "online": false,
+                "playerCount": 0i64,
+                "players": Bson::Array(vec![]),
+                "firstSeen": *first_seen,
+                "offlineSince": now_ts,
+                "expireAt": expire_at,
+            },
+            Some(doc! { "missedPolls": "", "missingSince": "" }),
+        ),
+        ServerWrite::Missing { server, missed_polls, missing_since } => (
+            server,
+            doc! {
+                "missedPolls": *missed_polls,
+                "missingSince": *missing_since,
+                "expireAt": expire_at,
+            },
+            None,
+        ),
+        ServerWrite::Offline { server, first_seen, offline_since } => (
+            server,
+            doc! {
+                "server": server.clone(),
+                "online": false,
+                "playerCount": 0i64,
+                "players": Bson::Array(vec![]),
+                "firstSeen": *first_seen,
+                "offlineSince": *offline_since,
+                "expireAt": expire_at,
+            },
+            Some(doc! { "missedPolls": "", "missingSince": "" }),
+        ),
+        ServerWrite::Delete { .. } => return None,
+    };
+    if !matches!(write, ServerWrite::Missing { .. }) {
+        set.extend(parse_server_name(server).fields());
+    }
+
+    let mut update = doc! { "$set": set };
+    if let Some(unset) = unset {
+        update.insert("$unset", unset);
+    }
+
+    Some(doc! {
+        "q": { "server": server.clone() },
+        "u": update,
+        "upsert": true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    // Mark offline on the first missed poll, delete after a minute offline
+    const IMMEDIATE: SyncSettings = SyncSettings {
+        offline_after_polls: 1,
+        offline_delete_secs: 60,
+        data_ttl_secs: 60 * 60 * 12,
+    };
+
+    const HYSTERESIS: SyncSettings = SyncSettings {
+        offline_after_polls: 3,
+        ..IMMEDIATE
+    };
+
+    fn current(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(s, ps)| (s.to_string(), ps.iter().map(|p| p.to_string()).collect()))
+            .collect()
+    }
+
+    fn stored(entries: &[(&str, Option<i64>, Option<i64>)]) -> HashMap<String, StoredServer> {
+        entries
+            .iter()
+            .map(|(s, first_seen, offline_since)| {
+                (
+                    s.to_string(),
+                    StoredServer { first_seen: *first_seen, offline_since: *offline_since, ..Default::default() },
+                )
+            })
+            .collect()
+    }
+
+    fn missing(server: &str, first_seen: i64, missed_polls: i64, missing_since: i64) -> HashMap<String, StoredServer> {
+        HashMap::from([(
+            server.to_string(),
+            StoredServer {
+                first_seen: Some(first_seen),
+                offline_since: None,
+                missed_polls,
+                missing_since: Some(missing_since),
+                players: vec![],
+            },
+        )])
+    }
+
+    #[test]
+    fn new_server_is_added_with_first_seen_now() {
+        let diff = diff_servers(&current(&[("WC1", &["alice"])]), &HashMap::new(), NOW, &IMMEDIATE);
+
+        assert_eq!(diff.added, vec!["WC1"]);
+        assert_eq!(diff.unchanged, 0);
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Online {
+                server: "WC1".into(),
+                players: vec!["alice".into()],
+                first_seen: NOW,
+            }]
+        );
+    }
+
+    #[test]
+    fn existing_online_server_keeps_first_seen() {
+        let diff = diff_servers(
+            &current(&[("WC1", &["alice", "bob"])]),
+            &stored(&[("WC1", Some(NOW - 3600), None)]),
+            NOW,
+            &IMMEDIATE,
+        );
+
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Online {
+                server: "WC1".into(),
+                players: vec!["alice".into(), "bob".into()],
+                first_seen: NOW - 3600,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_server_is_marked_offline_within_grace() {
+        let diff = diff_servers(&HashMap::new(), &stored(&[("WC2", Some(NOW - 600), None)]), NOW, &IMMEDIATE);
+
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Offline {
+                server: "WC2".into(),
+                first_seen: NOW - 600,
+                offline_since: NOW,
+            }]
+        );
+        assert_eq!(diff.offline(), vec!["WC2"]);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn missing_server_keeps_original_offline_since() {
+        let since = NOW - IMMEDIATE.offline_delete_secs;
+        let diff = diff_servers(
+            &HashMap::new(),
+            &stored(&[("WC2", Some(NOW - 600), Some(since))]),
+            NOW,
+            &IMMEDIATE,
+        );
+
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Offline {
+                server: "WC2".into(),
+                first_seen: NOW - 600,
+                offline_since: since,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_server_is_deleted_after_grace() {
+        let since = NOW - IMMEDIATE.offline_delete_secs - 1;
+        let diff = diff_servers(
+            &HashMap::new(),
+            &stored(&[("WC3", Some(NOW - 600), Some(since))]),
+            NOW,
+            &IMMEDIATE,
+        );
+
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Delete {
+                server: "WC3".into(),
+                first_seen: NOW - 600,
+                offline_since: since,
+            }]
+        );
+        assert_eq!(diff.removed, vec!["WC3"]);
+        assert!(diff.offline().is_empty());
+    }
+
+    #[test]
+    fn mixed_snapshot_orders_offline_before_online() {
+        let diff = diff_servers(
+            &current(&[("WC2", &["carol"]), ("WC1", &["alice"])]),
+            &stored(&[
+                ("WC1", Some(NOW - 100), None),
+                ("WC9", Some(NOW - 100), None),
+                ("WC8", Some(NOW - 900), Some(NOW - 900)),
+            ]),
+            NOW,
+            &IMMEDIATE,
+        );
+
+        let order: Vec<&str> = diff
+            .writes
+            .iter()
+            .map(|w| match w {
+                ServerWrite::Online { server, .. }
+                | ServerWrite::Missing { server, .. }
+                | ServerWrite::Offline { server, .. }
+                | ServerWrite::Delete { server, .. } => server.as_str(),
+            })
+            .collect();
+        assert_eq!(order, vec!["WC8", "WC9", "WC1", "WC2"]);
+        assert_eq!(diff.added, vec!["WC2"]);
+        assert_eq!(diff.removed, vec!["WC8"]);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.online(), vec!["WC1", "WC2"]);
+        assert_eq!(diff.offline(), vec!["WC9"]);
+    }
+
+    #[test]
+    fn first_missed_poll_only_counts() {
+        let diff = diff_servers(&HashMap::new(), &stored(&[("WC1", Some(NOW - 600), None)]), NOW, &HYSTERESIS);
+
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Missing {
+                server: "WC1".into(),
+                missed_polls: 1,
+                missing_since: NOW,
+            }]
+        );
+        assert_eq!(diff.online(), vec!["WC1"]);
+        assert!(diff.offline().is_empty());
+    }
+
+    #[test]
+    fn offline_after_enough_missed_polls_since_first_miss() {
+        let diff = diff_servers(&HashMap::new(), &missing("WC1", NOW - 600, 2, NOW - 70), NOW, &HYSTERESIS);
+
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Offline {
+                server: "WC1".into(),
+                first_seen: NOW - 600,
+                offline_since: NOW - 70,
+            }]
+        );
+    }
+
+    #[test]
+    fn reappearing_before_offline_keeps_first_seen() {
+        let diff = diff_servers(
+            &current(&[("WC1", &["alice"])]),
+            &missing("WC1", NOW - 600, 2, NOW - 70),
+            NOW,
+            &HYSTERESIS,
+        );
+
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.writes,
+            vec![ServerWrite::Online {
+                server: "WC1".into(),
+                players: vec!["alice".into()],
+                first_seen: NOW - 600,
+            }]
+        );
+    }
+
+    #[test]
+    fn without_destructive_keeps_only_upserts() {
+        let diff = diff_servers(
+            &current(&[("WC1", &["alice"])]),
+            &stored(&[("WC2", Some(NOW - 600), None), ("WC3", Some(NOW - 600), Some(NOW - 600))]),
+            NOW,
+            &IMMEDIATE,
+        )
+        .without_destructive();
+
+        assert_eq!(diff.online(), vec!["WC1"]);
+        assert!(diff.offline().is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.writes.len(), 1);
+    }
+
+    #[test]
+    fn update_statement_sets_online_fields() {
+        let write = ServerWrite::Online {
+            server: "WC1".into(),
+            players: vec!["alice".into()],
+            first_seen: NOW - 10,
+        };
+        let stmt = update_statement(&write, NOW, &IMMEDIATE).unwrap();
+        let set = stmt.get_document("u").unwrap().get_document("$set").unwrap();
+
+        assert_eq!(stmt.get_document("q").unwrap(), &doc! { "server": "WC1" });
+        assert!(stmt.get_bool("upsert").unwrap());
+        assert!(set.get_bool("online").unwrap());
+        assert_eq!(set.get_i64("playerCount").unwrap(), 1);
+        assert_eq!(set.get_i64("firstSeen").unwrap(), NOW - 10);
+        assert!(set.get("offlineSince").is_none());
+        assert_eq!(set.get_str("region").unwrap(), "WC");
+        assert_eq!(set.get_str("category").unwrap(), "normal");
+        assert_eq!(
+            stmt.get_document("u").unwrap().get_document("$unset").unwrap(),
+            &doc! { "offlineSince": "", "missedPolls": "", "missingSince": "" }
+        );
+    }
+
+    #[test]
+    fn missing_update_leaves_online_state_alone() {
+        let write = ServerWrite::Missing {
+            server: "WC1".into(),
+            missed_polls: 1,
+            missing_since: NOW,
+        };
+        let stmt = update_statement(&write, NOW, &HYSTERESIS).unwrap();
+        let set = stmt.get_document("u").unwrap().get_document("$set").unwrap();
+
+        assert!(set.get("online").is_none());
+        assert!(set.get("players").is_none());
+        assert_eq!(set.get_i64("missedPolls").unwrap(), 1);
+    }
+
+    #[test]
+    fn delete_has_no_update_statement() {
+        let write = ServerWrite::Delete {
+            server: "WC1".into(),
+            first_seen: NOW - 600,
+            offline_since: NOW - 120,
+        };
+        assert!(update_statement(&write, NOW, &IMMEDIATE).is_none());
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    Collection, Database, IndexModel,
+};
+use tokio::sync::OnceCell;
+use tracing::{info_span, Instrument};
+
+use super::diff::{ServerDiff, ServerWrite};
+use crate::mongo_client::run_write_command;
+
+const LIFECYCLE_COLLECTION: &str = "server_lifecycle";
+
+// A server coming back within this long after its shutdown counts as a restart
+const RESTART_WINDOW_SECS: i64 = 60 * 30;
+
+static INDEX_READY: OnceCell<()> = OnceCell::const_new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleKind {
+    Boot,
+    Shutdown,
+    Restart,
+}
+
+impl LifecycleKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LifecycleKind::Boot => "boot",
+            LifecycleKind::Shutdown => "shutdown",
+            LifecycleKind::Restart => "restart",
+        }
+    }
+}
+
+/// The most recent recorded shutdown of a server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastShutdown {
+    pub at: i64,
+    pub uptime_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifecycleEvent {
+    pub server: String,
+    pub kind: LifecycleKind,
+    pub at: i64,
+    /// Shutdown: how long the server was up. Restart: uptime before the restart.
+    pub uptime_secs: Option<i64>,
+    /// Restart: how long the server was gone.
+    pub downtime_secs: Option<i64>,
+}
+
+/// Derive lifecycle events from a reconciliation.
+///
+/// A deleted server shut down when it first went missing (`offlineSince`). A
+/// newly added server is a restart if it shut down within
+/// `RESTART_WINDOW_SECS`, otherwise a fresh boot.
+pub fn lifecycle_events(
+    diff: &ServerDiff,
+    last_shutdowns: &HashMap<String, LastShutdown>,
+    now_ts: i64,
+) -> Vec<LifecycleEvent> {
+    let mut events = Vec::new();
+
+    for write in &diff.writes {
+        if let ServerWrite::Delete { server, first_seen, offline_since } = write {
+            events.push(LifecycleEvent {
+                server: server.clone(),
+                kind: LifecycleKind::Shutdown,
+                at: *offline_since,
+                uptime_secs: Some(offline_since - first_seen),
+                downtime_secs: None,
+            });
+        }
+    }
+
+    for server in &diff.added {
+        let event = match last_shutdowns.get(server) {
+            Some(last) if now_ts - last.at <= RESTART_WINDOW_SECS => LifecycleEvent {
+                server: server.clone(),
+                kind: LifecycleKind::Restart,
+                at: now_ts,
+                uptime_secs: last.uptime_secs,
+                downtime_secs: Some(now_ts - last.at),
+            },
+            _ => LifecycleEvent {
+                server: server.clone(),
+                kind: LifecycleKind::Boot,
+                at: now_ts,
+                uptime_secs: None,
+                downtime_secs: None,
+            },
+        };
+        events.push(event);
+    }
+
+    events
+}
+
+/// Upsert statement for one event, keyed on (server, event, at). A shutdown's
+/// `at` is the stored `offlineSince`, so recording it again after a failed
+/// tick matches the existing row instead of adding another.
+pub fn lifecycle_statement(e: &LifecycleEvent) -> Document {
+    doc! {
+        "q": {
+            "server": &e.server,
+            "event": e.kind.as_str(),
+            "at": BsonDateTime::from_millis(e.at * 1000),
+        },
+        "u": { "$setOnInsert": {
+            "uptimeSecs": e.uptime_secs.map(Bson::Int64).unwrap_or(Bson::Null),
+            "downtimeSecs": e.downtime_secs.map(Bson::Int64).unwrap_or(Bson::Null),
+        }},
+        "upsert": true,
+    }
+}
+
+async fn create_index(coll: &Collection<Document>) -> Result<()> {
+    let idx = IndexModel::builder()
+        .keys(doc! { "server": 1, "event": 1, "at": -1 })
+        .build();
+    coll.create_index(idx, None)
+        .await
+        .context("creating server_lifecycle index")?;
+    Ok(())
+}
+
+/// Record boot/shutdown/restart events implied by `diff` in `server_lifecycle`.
+///
+/// Runs before the server writes: once a server doc is deleted, nothing is
+/// left to derive its shutdown from.
+pub async fn record_lifecycle(db: &Database, diff: &ServerDiff, now_ts: i64) -> Result<Vec<LifecycleEvent>> {
+    let coll = db.collection::<Document>(LIFECYCLE_COLLECTION);
+    INDEX_READY.get_or_try_init(|| create_index(&coll)).await?;
+
+    let mut last_shutdowns: HashMap<String, LastShutdown> = HashMap::new();
+    if !diff.added.is_empty() {
+        let pipeline = vec![
+            doc! { "$match": { "server": { "$in": diff.added.clone() }, "event": "shutdown" } },
+            doc! { "$sort": { "at": -1 } },
+            doc! { "$group": {
+                "_id": "$server",
+                "at": { "$first": "$at" },
+                "uptimeSecs": { "$first": "$uptimeSecs" },
+            }},
+        ];
+        let rows: Vec<Document> = async { coll.aggregate(pipeline, None).await?.try_collect().await }
+            .instrument(info_span!("mongo.aggregate", collection = LIFECYCLE_COLLECTION))
+            .await
+            .context("loading last server shutdowns")?;
+        for row in rows {
+            let (Ok(server), Ok(at)) = (row.get_str("_id"), row.get_datetime("at")) else {
+                continue;
+            };
+            last_shutdowns.insert(
+                server.to_string(),
+                LastShutdown {
+                    at: at.timestamp_millis() / 1000,
+                    uptime_secs: row.get_i64("uptimeSecs").ok(),
+                },
+            );
+        }
+    }
+
+    let events = lifecycle_events(diff, &last_shutdowns, now_ts);
+    if events.is_empty() {
+        return Ok(events);
+    }
+
+    let updates: Vec<Document> = events.iter().map(lifecycle_statement).collect();
+    let count = updates.len();
+    run_write_command(db, doc! { "update": LIFECYCLE_COLLECTION, "updates": updates, "ordered": true })
+        .instrument(info_span!("mongo.bulk_update", collection = LIFECYCLE_COLLECTION, count))
+        .await
+        .context("recording server lifecycle events")?;
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn delete_is_shutdown_at_offline_since() {
+        let diff = ServerDiff {
+            writes: vec![ServerWrite::Delete {
+                server: "WC1".into(),
+                first_seen: NOW - 7200,
+                offline_since: NOW - 120,
+            }],
+            removed: vec!["WC1".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            lifecycle_events(&diff, &HashMap::new(), NOW),
+            vec![LifecycleEvent {
+                server: "WC1".into(),
+                kind: LifecycleKind::Shutdown,
+                at: NOW - 120,
+                uptime_secs: Some(7080),
+                downtime_secs: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn added_server_is_restart_only_within_window() {
+        let diff = ServerDiff {
+            added: vec!["WC1".into(), "WC2".into()],
+            ..Default::default()
+        };
+        let last_shutdowns = HashMap::from([
+            ("WC1".to_string(), LastShutdown { at: NOW - 300, uptime_secs: Some(36000) }),
+            ("WC2".to_string(), LastShutdown { at: NOW - RESTART_WINDOW_SECS - 1, uptime_secs: Some(36000) }),
+        ]);
+
+        let events = lifecycle_events(&diff, &last_shutdowns, NOW);
+        assert_eq!(events[0].kind, LifecycleKind::Restart);
+        assert_eq!(events[0].downtime_secs, Some(300));
+        assert_eq!(events[0].uptime_secs, Some(36000));
+        assert_eq!(events[1].kind, LifecycleKind::Boot);
+        assert_eq!(events[1].downtime_secs, None);
+    }
+
+    #[test]
+    fn shutdown_is_keyed_on_offline_since() {
+        let shutdown = LifecycleEvent {
+            server: "WC1".into(),
+            kind: LifecycleKind::Shutdown,
+            at: NOW - 120,
+            uptime_secs: Some(7080),
+            downtime_secs: None,
+        };
+        let statement = lifecycle_statement(&shutdown);
+
+        assert_eq!(
+            statement.get_document("q").unwrap(),
+            &doc! { "server": "WC1", "event": "shutdown", "at": BsonDateTime::from_millis((NOW - 120) * 1000) }
+        );
+        let insert = statement.get_document("u").unwrap().get_document("$setOnInsert").unwrap();
+        assert_eq!(insert.get_i64("uptimeSecs").unwrap(), 7080);
+    }
+}
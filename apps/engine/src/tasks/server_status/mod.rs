@@ -0,0 +1,317 @@
+mod anomaly;
+mod diff;
+mod lifecycle;
+mod population;
+mod region;
+mod snapshot;
+
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde_json::Value;
+
+use mongodb::{bson::{doc, Bson, DateTime as BsonDateTime, Document}, Database, IndexModel};
+use mongodb::options::IndexOptions;
+use futures_util::stream::TryStreamExt;
+use std::time::Duration;
+use tracing::{info_span, Instrument};
+
+use crate::logger::{log_error, log_event};
+use crate::mongo_client::{database, run_write_command};
+use crate::scheduler::failure::UnexpectedPayload;
+use crate::scheduler::node::{TaskFuture, TaskSummary};
+use crate::tasks::player_counts::record_player_counts;
+use crate::tasks::sessions::{track_sessions, PresenceEvent, SessionDiff};
+use crate::tasks::watchlist::notify_watchlist;
+use wynnpool_engine_macros::fetch;
+
+use anomaly::{assess, load_guard, record_assessment, AnomalySettings};
+use diff::{diff_servers, update_statement, ServerWrite, StoredServer, SyncSettings};
+use lifecycle::record_lifecycle;
+use population::{record_population_stats, PopulationSample};
+use region::{record_region_totals, region_totals};
+use snapshot::{publish_status, status_snapshot};
+
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+const PLAYERS_URL: &str = "https://api.wynncraft.com/v3/player";
+
+#[fetch(interval = 35)]
+fn update_server_status_new() -> TaskFuture {
+    Box::pin(run_update_server_status_new())
+}
+
+async fn run_update_server_status_new() -> Result<TaskSummary> {
+    let whole_start = Instant::now();
+    log_event("TASK", "fetching server list", None);
+
+    // --- 1. HTTP FETCH ---
+    let http_start = Instant::now();
+    let resp: Value = async {
+        CLIENT
+            .get(PLAYERS_URL)
+            .send()
+            .await
+            .context("requesting /v3/player")?
+            .error_for_status()
+            .context("requesting /v3/player")?
+            .json()
+            .await
+            .context("decoding /v3/player response")
+    }
+    .instrument(info_span!("http.fetch", url = PLAYERS_URL))
+    .await?;
+    let http_elapsed = http_start.elapsed();
+
+    let players = resp["players"]
+        .as_object()
+        .ok_or(UnexpectedPayload("Invalid players format"))?;
+
+    // Build server → player list map (and player → server for session tracking)
+    let mut servers: HashMap<String, Vec<String>> = HashMap::new();
+    let mut player_servers: HashMap<String, String> = HashMap::with_capacity(players.len());
+    for (player_name, server_name_value) in players {
+        let server = server_name_value
+            .as_str()
+            .unwrap_or("UNKNOWN")
+            .to_string();
+
+        player_servers.insert(player_name.clone(), server.clone());
+        servers.entry(server).or_default().push(player_name.clone());
+    }
+
+    // --- 2. MONGODB PART ---
+    let mongo_start = Instant::now();
+
+    let db = database().await?;
+    let coll = db.collection::<Document>("wynncraft_servers");
+
+    // Ensure TTL index on `expireAt` so documents are removed by MongoDB after expiry
+    let idx = IndexModel::builder()
+        .keys(doc! { "expireAt": 1 })
+        .options(IndexOptions::builder().expire_after(Some(Duration::from_secs(0))).build())
+        .build();
+    let _ = coll
+        .create_index(idx, None)
+        .await
+        .context("creating wynncraft_servers TTL index")?;
+    let idx = IndexModel::builder().keys(doc! { "region": 1, "server": 1 }).build();
+    let _ = coll
+        .create_index(idx, None)
+        .await
+        .context("creating wynncraft_servers region index")?;
+
+    // Fetch existing server docs
+    let existing_docs: Vec<Document> = async {
+        coll.find(None, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.find", collection = "wynncraft_servers"))
+    .await
+    .context("loading existing wynncraft_servers")?;
+
+    let mut existing: HashMap<String, StoredServer> = HashMap::new();
+    for doc in &existing_docs {
+        if let Some(Bson::String(name)) = doc.get("server") {
+            existing.insert(name.clone(), StoredServer::from_doc(doc));
+        }
+    }
+
+    let now_ts: i64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    // Sanity-check the snapshot against the last trusted one before acting on it
+    let totals = region_totals(&servers);
+    let sample = PopulationSample::new(&totals);
+    let guard = load_guard(&db).await?;
+    let assessment = assess(&sample, &guard, &AnomalySettings::from_config());
+    record_assessment(&db, &assessment, &sample, now_ts).await?;
+    for anomaly in &assessment.anomalies {
+        log_event(
+            "WARN",
+            &format!(
+                "server status: {}{}",
+                anomaly.describe(),
+                if assessment.suspect { ", holding back destructive and history writes" } else { ", accepted as stable" }
+            ),
+            None,
+        );
+    }
+
+    // Reconcile in memory, then apply every write in a constant number of round trips
+    let plan = assessment.write_plan();
+    let settings = SyncSettings::from_config();
+    let mut diff = diff_servers(&servers, &existing, now_ts, &settings);
+    if !plan.destructive {
+        diff = diff.without_destructive();
+    }
+    let lifecycle = record_lifecycle(&db, &diff, now_ts).await?;
+    apply_server_writes(&db, &diff.writes, now_ts, &settings).await?;
+    for event in &lifecycle {
+        log_event("LIFECYCLE", &format!("{} {}", event.server, event.kind.as_str()), None);
+    }
+
+    // A suspect sample would put a dip that never happened into the graphs
+    let (regions, points) = if plan.history {
+        let regions = record_region_totals(&db, &totals, now_ts).await?;
+        record_population_stats(&db, &sample, now_ts).await?;
+        // Population history for graphs; downsampled by `downsample_player_counts`
+        let points = record_player_counts(&db, &servers, BsonDateTime::from_millis(now_ts * 1000)).await?;
+        (regions, points)
+    } else {
+        (0, 0)
+    };
+
+    // A partial player list would close the sessions of everyone missing from it
+    let sessions = if plan.sessions {
+        track_sessions(&db, &player_servers, now_ts).await?
+    } else {
+        SessionDiff::default()
+    };
+    let (mut joins, mut leaves, mut switches) = (0usize, 0usize, 0usize);
+    for event in &sessions.events {
+        match event {
+            PresenceEvent::Join { .. } => joins += 1,
+            PresenceEvent::Leave { .. } => leaves += 1,
+            PresenceEvent::Switch { .. } => switches += 1,
+        }
+    }
+
+    let watched = notify_watchlist(&db, &sessions.events, now_ts).await?;
+
+    let mongo_elapsed = mongo_start.elapsed();
+
+    // Cache the computed status for the API and bot; Mongo stays the source of
+    // truth, so a Redis outage is logged rather than failing the tick. A suspect
+    // snapshot leaves the last trusted status in place.
+    let published = if plan.publish {
+        match publish_status(status_snapshot(&diff, &existing, now_ts), &diff).await {
+            Ok(published) => published,
+            Err(e) => {
+                log_error("server status: caching snapshot in Redis failed", &e, None);
+                false
+            }
+        }
+    } else {
+        false
+    };
+    let whole_elapsed = whole_start.elapsed();
+
+    // Build debug lists for summary
+    let added_str = if diff.added.is_empty() {
+        "-".to_string()
+    } else {
+        diff.added.join(",")
+    };
+
+    let removed_str = if diff.removed.is_empty() {
+        "-".to_string()
+    } else {
+        diff.removed.join(",")
+    };
+
+    let online_now = diff.online();
+    let offline_now = diff.offline();
+
+    let online_now_str = if online_now.is_empty() {
+        "-".to_string()
+    } else {
+        online_now.join(",")
+    };
+
+    let offline_now_str = if offline_now.is_empty() {
+        "-".to_string()
+    } else {
+        offline_now.join(",")
+    };
+
+    log_event(
+        "SUMMARY",
+        &format!(
+            "servers: added={} [{}], removed={} [{}], unchanged={} | online=[{}] offline=[{}] | players: joined={} left={} switched={} (http={}ms, mongo={}ms)",
+            diff.added.len(),
+            added_str,
+            diff.removed.len(),
+            removed_str,
+            diff.unchanged,
+            online_now_str,
+            offline_now_str,
+            joins,
+            leaves,
+            switches,
+            http_elapsed.as_millis(),
+            mongo_elapsed.as_millis()
+        ),
+        Some(whole_elapsed),
+    );
+
+    Ok(doc! {
+        "added": diff.added.len() as i64,
+        "removed": diff.removed.len() as i64,
+        "unchanged": diff.unchanged as i64,
+        "online": online_now.len() as i64,
+        "offline": offline_now.len() as i64,
+        "playerCountPoints": points as i64,
+        "joined": joins as i64,
+        "left": leaves as i64,
+        "switched": switches as i64,
+        "sessionsClosed": sessions.closed.len() as i64,
+        "watchedEvents": watched as i64,
+        "lifecycleEvents": lifecycle.len() as i64,
+        "regions": regions as i64,
+        "statusPublished": published,
+        "anomalies": assessment.anomalies.len() as i64,
+        "suspect": assessment.suspect,
+    })
+}
+
+/// Apply reconciled writes: one ordered `update` command with every upsert and
+/// offline mark, then one ordered `delete` command when any server is due for
+/// removal.
+///
+/// A write command carries a single statement type and the 2.x driver has no
+/// `bulk_write`, so this is two round trips and not atomic. Deletes go last
+/// because they are the only writes that cannot be derived again: if the
+/// delete fails, the servers are still stored offline and the next tick
+/// computes the same deletes; if the update fails, nothing has been removed
+/// yet.
+async fn apply_server_writes(
+    db: &Database,
+    writes: &[ServerWrite],
+    now_ts: i64,
+    settings: &SyncSettings,
+) -> Result<()> {
+    let updates: Vec<Document> = writes
+        .iter()
+        .filter_map(|w| update_statement(w, now_ts, settings))
+        .collect();
+    let deletes: Vec<Document> = writes
+        .iter()
+        .filter_map(|w| match w {
+            ServerWrite::Delete { server, .. } => Some(doc! { "q": { "server": server.clone() }, "limit": 1 }),
+            _ => None,
+        })
+        .collect();
+
+    if !updates.is_empty() {
+        let count = updates.len();
+        run_write_command(db, doc! { "update": "wynncraft_servers", "updates": updates, "ordered": true })
+            .instrument(info_span!("mongo.bulk_update", collection = "wynncraft_servers", count))
+            .await
+            .context("upserting servers")?;
+    }
+
+    if !deletes.is_empty() {
+        let count = deletes.len();
+        run_write_command(db, doc! { "delete": "wynncraft_servers", "deletes": deletes, "ordered": true })
+            .instrument(info_span!("mongo.bulk_delete", collection = "wynncraft_servers", count))
+            .await
+            .context("deleting offline servers")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use mongodb::{
+    bson::{doc, DateTime as BsonDateTime, Document},
+    Database,
+};
+use tracing::{info_span, Instrument};
+
+use super::region::RegionTotal;
+use crate::mongo_client::run_write_command;
+
+const POPULATION_COLLECTION: &str = "population_stats";
+
+/// One poll's worth of population, already grouped by region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PopulationSample {
+    pub online: i64,
+    pub regions: BTreeMap<String, i64>,
+}
+
+impl PopulationSample {
+    pub fn new(totals: &BTreeMap<String, RegionTotal>) -> Self {
+        let regions: BTreeMap<String, i64> = totals.iter().map(|(r, t)| (r.clone(), t.players)).collect();
+        PopulationSample {
+            online: regions.values().sum(),
+            regions,
+        }
+    }
+}
+
+/// Peak tracking for the period doc `_id`: raise `peak` and move `peakAt` only
+/// when this sample beats it. A pipeline update so both fields move together.
+fn peak_statement(id: String, period: &str, start: BsonDateTime, online: i64, at: BsonDateTime) -> Document {
+    doc! {
+        "q": { "_id": id },
+        "u": [{ "$set": {
+            "period": period,
+            "start": start,
+            "peakAt": { "$cond": [{ "$gt": [online, { "$ifNull": ["$peak", -1] }] }, at, "$peakAt"] },
+            "peak": { "$max": [{ "$ifNull": ["$peak", -1] }, online] },
+            "updatedAt": at,
+        }}],
+        "upsert": true,
+    }
+}
+
+/// The `update` statements folding one sample into `population_stats`.
+///
+/// Docs, by `_id`:
+/// - `current`: online now and each region's count and share of it
+/// - `day:<YYYY-MM-DD>` / `week:<ISO year>-W<ISO week>`: peak with timestamp (UTC)
+/// - `hourOfWeek:<0..167>`: running sum, sample count and average, Monday 00:00 UTC = 0
+///
+/// Pure: the time comes from `now_ts`.
+pub fn population_statements(sample: &PopulationSample, now_ts: i64) -> Vec<Document> {
+    let now = DateTime::<Utc>::from_timestamp(now_ts, 0).unwrap_or_default();
+    let at = BsonDateTime::from_millis(now_ts * 1000);
+
+    let mut regions = Document::new();
+    for (region, players) in &sample.regions {
+        let share = if sample.online > 0 {
+            *players as f64 / sample.online as f64
+        } else {
+            0.0
+        };
+        regions.insert(region.clone(), doc! { "players": *players, "share": share });
+    }
+
+    let day_start = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let days_from_monday = now.weekday().num_days_from_monday() as i64;
+    let week_start = day_start - chrono::Duration::days(days_from_monday);
+    let iso = now.iso_week();
+    let hour_of_week = days_from_monday * 24 + now.hour() as i64;
+
+    vec![
+        doc! {
+            "q": { "_id": "current" },
+            "u": { "$set": { "online": sample.online, "regions": regions, "updatedAt": at } },
+            "upsert": true,
+        },
+        peak_statement(
+            format!("day:{}", now.format("%Y-%m-%d")),
+            "day",
+            BsonDateTime::from_millis(day_start.timestamp_millis()),
+            sample.online,
+            at,
+        ),
+        peak_statement(
+            format!("week:{}-W{:02}", iso.year(), iso.week()),
+            "week",
+            BsonDateTime::from_millis(week_start.timestamp_millis()),
+            sample.online,
+            at,
+        ),
+        doc! {
+            "q": { "_id": format!("hourOfWeek:{hour_of_week}") },
+            "u": [
+                { "$set": {
+                    "period": "hourOfWeek",
+                    "hourOfWeek": hour_of_week,
+                    "sum": { "$add": [{ "$ifNull": ["$sum", 0] }, sample.online] },
+                    "samples": { "$add": [{ "$ifNull": ["$samples", 0] }, 1] },
+                    "updatedAt": at,
+                }},
+                { "$set": { "avg": { "$divide": ["$sum", "$samples"] } } },
+            ],
+            "upsert": true,
+        },
+    ]
+}
+
+/// Fold the current poll into the rolling aggregates in `population_stats`.
+pub async fn record_population_stats(db: &Database, sample: &PopulationSample, now_ts: i64) -> Result<()> {
+    let updates = population_statements(sample, now_ts);
+    let count = updates.len();
+    run_write_command(db, doc! { "update": POPULATION_COLLECTION, "updates": updates, "ordered": true })
+        .instrument(info_span!("mongo.bulk_update", collection = POPULATION_COLLECTION, count))
+        .await
+        .context("updating population stats")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tuesday 2023-11-14 22:13:20 UTC
+    const NOW: i64 = 1_700_000_000;
+
+    fn sample() -> PopulationSample {
+        PopulationSample {
+            online: 40,
+            regions: BTreeMap::from([("EU".to_string(), 30), ("NA".to_string(), 10)]),
+        }
+    }
+
+    #[test]
+    fn period_ids_follow_utc_calendar() {
+        let ids: Vec<String> = population_statements(&sample(), NOW)
+            .iter()
+            .map(|s| s.get_document("q").unwrap().get_str("_id").unwrap().to_string())
+            .collect();
+
+        assert_eq!(ids, vec!["current", "day:2023-11-14", "week:2023-W46", "hourOfWeek:46"]);
+    }
+
+    #[test]
+    fn current_doc_carries_region_share() {
+        let statements = population_statements(&sample(), NOW);
+        let set = statements[0].get_document("u").unwrap().get_document("$set").unwrap();
+        let eu = set.get_document("regions").unwrap().get_document("EU").unwrap();
+
+        assert_eq!(set.get_i64("online").unwrap(), 40);
+        assert_eq!(eu.get_i64("players").unwrap(), 30);
+        assert_eq!(eu.get_f64("share").unwrap(), 0.75);
+    }
+
+    #[test]
+    fn week_starts_on_monday() {
+        let statements = population_statements(&sample(), NOW);
+        let stage = statements[2].get_array("u").unwrap()[0].as_document().unwrap();
+        let start = stage.get_document("$set").unwrap().get_datetime("start").unwrap();
+
+        // Monday 2023-11-13 00:00 UTC
+        assert_eq!(start.timestamp_millis(), 1_699_833_600_000);
+    }
+}
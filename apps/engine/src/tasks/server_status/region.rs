@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    Database,
+};
+use tracing::{info_span, Instrument};
+
+use crate::mongo_client::run_write_command;
+
+const REGIONS_COLLECTION: &str = "wynncraft_regions";
+
+// Prefixes of regular numbered worlds (`WC` is the legacy unprefixed naming)
+const KNOWN_REGIONS: &[&str] = &["NA", "EU", "AS", "SA", "OC", "WC"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerCategory {
+    Normal,
+    Youtube,
+    Beta,
+    Unknown,
+}
+
+impl ServerCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServerCategory::Normal => "normal",
+            ServerCategory::Youtube => "youtube",
+            ServerCategory::Beta => "beta",
+            ServerCategory::Unknown => "unknown",
+        }
+    }
+}
+
+/// A server name split into its structured parts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerName {
+    /// Upper-case region prefix for regular worlds, e.g. `EU`.
+    pub region: Option<String>,
+    pub number: Option<i64>,
+    pub category: ServerCategory,
+}
+
+impl ServerName {
+    /// Region totals key: the region for regular worlds, the category otherwise.
+    pub fn group(&self) -> String {
+        match &self.region {
+            Some(region) => region.clone(),
+            None => self.category.as_str().to_string(),
+        }
+    }
+
+    /// Fields stored on the `wynncraft_servers` doc.
+    pub fn fields(&self) -> Document {
+        doc! {
+            "region": self.region.clone().map(Bson::String).unwrap_or(Bson::Null),
+            "number": self.number.map(Bson::Int64).unwrap_or(Bson::Null),
+            "category": self.category.as_str(),
+        }
+    }
+}
+
+/// Parse a server name such as `WC1`, `EU3`, `YT` or `BETA`.
+///
+/// Anything that is not a known region prefix followed by a number, or one of
+/// the special worlds, is `Unknown` rather than an error: new naming schemes
+/// should still be stored, just not grouped.
+pub fn parse_server_name(name: &str) -> ServerName {
+    let upper = name.trim().to_ascii_uppercase();
+    let split = upper.find(|c: char| c.is_ascii_digit()).unwrap_or(upper.len());
+    let (prefix, digits) = upper.split_at(split);
+    let number = digits.parse::<i64>().ok();
+
+    let category = match prefix {
+        "YT" => ServerCategory::Youtube,
+        "BETA" | "BT" => ServerCategory::Beta,
+        p if KNOWN_REGIONS.contains(&p) && number.is_some() => ServerCategory::Normal,
+        _ => ServerCategory::Unknown,
+    };
+
+    ServerName {
+        region: (category == ServerCategory::Normal).then(|| prefix.to_string()),
+        number: if digits.is_empty() { None } else { number },
+        category,
+    }
+}
+
+/// Whether a region totals key is a regular region rather than the `youtube`,
+/// `beta` or `unknown` group of special worlds.
+pub fn is_regular_region(group: &str) -> bool {
+    KNOWN_REGIONS.contains(&group)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionTotal {
+    pub servers: i64,
+    pub players: i64,
+}
+
+/// Sum the current snapshot per region (or category for special worlds).
+pub fn region_totals(servers: &HashMap<String, Vec<String>>) -> BTreeMap<String, RegionTotal> {
+    let mut totals: BTreeMap<String, RegionTotal> = BTreeMap::new();
+    for (server, players) in servers {
+        let total = totals.entry(parse_server_name(server).group()).or_default();
+        total.servers += 1;
+        total.players += players.len() as i64;
+    }
+    totals
+}
+
+/// Replace `wynncraft_regions` with the totals of the current snapshot.
+/// Regions absent from the snapshot are removed.
+pub async fn record_region_totals(
+    db: &Database,
+    totals: &BTreeMap<String, RegionTotal>,
+    now_ts: i64,
+) -> Result<usize> {
+    let updated_at = BsonDateTime::from_millis(now_ts * 1000);
+
+    let keys: Vec<Bson> = totals.keys().cloned().map(Bson::String).collect();
+    run_write_command(
+        db,
+        doc! {
+            "delete": REGIONS_COLLECTION,
+            "deletes": [{ "q": { "_id": { "$nin": keys } }, "limit": 0 }],
+        },
+    )
+    .instrument(info_span!("mongo.bulk_delete", collection = REGIONS_COLLECTION))
+    .await
+    .context("removing vanished regions")?;
+
+    if totals.is_empty() {
+        return Ok(0);
+    }
+
+    let updates: Vec<Document> = totals
+        .iter()
+        .map(|(region, total)| {
+            doc! {
+                "q": { "_id": region },
+                "u": { "$set": {
+                    "servers": total.servers,
+                    "playerCount": total.players,
+                    "updatedAt": updated_at,
+                }},
+                "upsert": true,
+            }
+        })
+        .collect();
+    let count = updates.len();
+    run_write_command(db, doc! { "update": REGIONS_COLLECTION, "updates": updates, "ordered": true })
+        .instrument(info_span!("mongo.bulk_update", collection = REGIONS_COLLECTION, count))
+        .await
+        .context("upserting region totals")?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_regular_and_special_names() {
+        let eu3 = parse_server_name("EU3");
+        assert_eq!(eu3.region.as_deref(), Some("EU"));
+        assert_eq!(eu3.number, Some(3));
+        assert_eq!(eu3.category, ServerCategory::Normal);
+
+        assert_eq!(parse_server_name("wc12").region.as_deref(), Some("WC"));
+        assert_eq!(parse_server_name("YT").category, ServerCategory::Youtube);
+        assert_eq!(parse_server_name("BETA").category, ServerCategory::Beta);
+        assert_eq!(parse_server_name("BETA").region, None);
+    }
+
+    #[test]
+    fn unrecognised_names_are_unknown() {
+        for name in ["UNKNOWN", "XX4", "EU", ""] {
+            let parsed = parse_server_name(name);
+            assert_eq!(parsed.category, ServerCategory::Unknown, "{name}");
+            assert_eq!(parsed.region, None, "{name}");
+        }
+        assert_eq!(parse_server_name("XX4").number, Some(4));
+    }
+
+    #[test]
+    fn totals_group_by_region_then_category() {
+        let servers: HashMap<String, Vec<String>> = HashMap::from([
+            ("EU1".to_string(), vec!["a".to_string(), "b".to_string()]),
+            ("EU2".to_string(), vec!["c".to_string()]),
+            ("AS1".to_string(), vec![]),
+            ("YT".to_string(), vec!["d".to_string()]),
+        ]);
+
+        let totals = region_totals(&servers);
+        assert_eq!(totals.keys().collect::<Vec<_>>(), vec!["AS", "EU", "youtube"]);
+        assert_eq!(totals["EU"], RegionTotal { servers: 2, players: 3 });
+        assert_eq!(totals["AS"], RegionTotal { servers: 1, players: 0 });
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+
+use super::diff::{ServerDiff, ServerWrite, StoredServer};
+use super::region::parse_server_name;
+use crate::config::{SERVER_STATUS_CHANNEL, SERVER_STATUS_KEY};
+use crate::redis_client::{get_json, publish_json, set_json};
+
+/// Build the status JSON served by the API, in the shape `ServerService::getStatus`
+/// computes from Mongo: `totalPlayer` plus per-server online, firstSeen,
+/// lastSeen, uptime, playerCount and players.
+///
+/// Pure: reflects the state after `diff` is applied. Deleted servers are left out.
+pub fn status_snapshot(diff: &ServerDiff, existing: &HashMap<String, StoredServer>, now_ts: i64) -> Value {
+    let mut servers = Map::new();
+    let mut total_player = 0usize;
+
+    for write in &diff.writes {
+        let (server, online, first_seen, last_seen, players) = match write {
+            ServerWrite::Online { server, players, first_seen } => {
+                let online = !players.is_empty();
+                (server, online, *first_seen, now_ts, players.clone())
+            }
+            ServerWrite::Missing { server, .. } => {
+                let stored = existing.get(server);
+                let first_seen = stored.and_then(|s| s.first_seen).unwrap_or(now_ts);
+                let players = stored.map(|s| s.players.clone()).unwrap_or_default();
+                (server, true, first_seen, now_ts, players)
+            }
+            ServerWrite::Offline { server, first_seen, offline_since } => {
+                (server, false, *first_seen, *offline_since, Vec::new())
+            }
+            ServerWrite::Delete { .. } => continue,
+        };
+
+        let name = parse_server_name(server);
+        total_player += players.len();
+        servers.insert(
+            server.clone(),
+            json!({
+                "online": online,
+                "firstSeen": first_seen,
+                "lastSeen": last_seen,
+                "uptime": (now_ts - first_seen).max(0),
+                "playerCount": players.len(),
+                "players": players,
+                "region": name.region,
+                "category": name.category.as_str(),
+            }),
+        );
+    }
+
+    json!({
+        "totalPlayer": total_player,
+        "updatedAt": now_ts,
+        "servers": servers,
+    })
+}
+
+/// The parts of a snapshot that constitute a change worth notifying about:
+/// which servers exist, whether they are online, and who is on them.
+fn fingerprint(snapshot: &Value) -> Vec<(String, bool, Vec<String>)> {
+    let mut out: Vec<(String, bool, Vec<String>)> = snapshot["servers"]
+        .as_object()
+        .map(|servers| {
+            servers
+                .iter()
+                .map(|(name, s)| {
+                    let mut players: Vec<String> = s["players"]
+                        .as_array()
+                        .map(|ps| ps.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+                        .unwrap_or_default();
+                    players.sort();
+                    (name.clone(), s["online"].as_bool().unwrap_or(false), players)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort();
+    out
+}
+
+/// Cache `snapshot` under `SERVER_STATUS_KEY` and, if it differs from the
+/// previously cached one, publish a notification on `SERVER_STATUS_CHANNEL`.
+/// Returns whether a notification was published.
+pub async fn publish_status(snapshot: Value, diff: &ServerDiff) -> Result<bool> {
+    let previous = get_json(&SERVER_STATUS_KEY).await?;
+    let changed = previous.as_ref().map(fingerprint) != Some(fingerprint(&snapshot));
+
+    let notification = json!({
+        "key": SERVER_STATUS_KEY.as_str(),
+        "updatedAt": snapshot["updatedAt"].clone(),
+        "totalPlayer": snapshot["totalPlayer"].clone(),
+        "added": diff.added,
+        "removed": diff.removed,
+    });
+
+    set_json(&SERVER_STATUS_KEY, snapshot).await?;
+    if changed {
+        publish_json(&SERVER_STATUS_CHANNEL, notification).await?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn snapshot_matches_api_shape() {
+        let diff = ServerDiff {
+            writes: vec![
+                ServerWrite::Offline { server: "EU1".into(), first_seen: NOW - 900, offline_since: NOW - 60 },
+                ServerWrite::Missing { server: "EU2".into(), missed_polls: 1, missing_since: NOW },
+                ServerWrite::Delete { server: "EU3".into(), first_seen: NOW - 900, offline_since: NOW - 600 },
+                ServerWrite::Online { server: "WC1".into(), players: vec!["alice".into()], first_seen: NOW - 300 },
+            ],
+            ..Default::default()
+        };
+        let existing = HashMap::from([(
+            "EU2".to_string(),
+            StoredServer { first_seen: Some(NOW - 100), players: vec!["bob".into(), "carol".into()], ..Default::default() },
+        )]);
+
+        let snapshot = status_snapshot(&diff, &existing, NOW);
+
+        assert_eq!(snapshot["totalPlayer"], 3);
+        assert!(snapshot["servers"].get("EU3").is_none());
+        assert_eq!(
+            snapshot["servers"]["WC1"],
+            json!({
+                "online": true,
+                "firstSeen": NOW - 300,
+                "lastSeen": NOW,
+                "uptime": 300,
+                "playerCount": 1,
+                "players": ["alice"],
+                "region": "WC",
+                "category": "normal",
+            })
+        );
+        assert_eq!(snapshot["servers"]["EU1"]["online"], false);
+        assert_eq!(snapshot["servers"]["EU1"]["lastSeen"], NOW - 60);
+        assert_eq!(snapshot["servers"]["EU2"]["playerCount"], 2);
+    }
+
+    #[test]
+    fn fingerprint_ignores_time_fields() {
+        let diff = ServerDiff {
+            writes: vec![ServerWrite::Online { server: "WC1".into(), players: vec!["alice".into()], first_seen: NOW }],
+            ..Default::default()
+        };
+        let earlier = status_snapshot(&diff, &HashMap::new(), NOW);
+        let later = status_snapshot(&diff, &HashMap::new(), NOW + 35);
+
+        assert_ne!(earlier, later);
+        assert_eq!(fingerprint(&earlier), fingerprint(&later));
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    options::IndexOptions,
+    Database, IndexModel,
+};
+use tokio::sync::OnceCell;
+use tracing::{info_span, Instrument};
+
+use crate::logger::log_error;
+use crate::mongo_client::run_write_command;
+
+/// Sessions currently in progress, one doc per online player (`_id` = player).
+const OPEN_COLLECTION: &str = "player_sessions_open";
+/// Finished sessions, kept for playtime statistics.
+const CLOSED_COLLECTION: &str = "player_sessions";
+
+// A player unseen for longer than this (e.g. across an engine outage) is
+// treated as having left at their last sighting, not at the current poll
+const SESSION_GAP_SECS: i64 = 60 * 3;
+
+static INDEXES_READY: OnceCell<()> = OnceCell::const_new();
+
+/// A session in progress, as stored in `player_sessions_open`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenSession {
+    pub server: String,
+    pub started_at: i64,
+    pub last_seen_at: i64,
+}
+
+/// A player presence change between two consecutive snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresenceEvent {
+    Join { player: String, server: String },
+    Leave { player: String, server: String },
+    Switch { player: String, from: String, to: String },
+}
+
+impl PresenceEvent {
+    pub fn player(&self) -> &str {
+        match self {
+            PresenceEvent::Join { player, .. }
+            | PresenceEvent::Leave { player, .. }
+            | PresenceEvent::Switch { player, .. } => player,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PresenceEvent::Join { .. } => "join",
+            PresenceEvent::Leave { .. } => "leave",
+            PresenceEvent::Switch { .. } => "switch",
+        }
+    }
+}
+
+/// A finished session, as stored in `player_sessions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedSession {
+    pub player: String,
+    pub server: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionDiff {
+    pub events: Vec<PresenceEvent>,
+    /// Sessions to persist as finished.
+    pub closed: Vec<ClosedSession>,
+    /// Players whose open session (re)starts now, with their server.
+    pub opened: Vec<(String, String)>,
+    /// Players whose open session ends without a new one.
+    pub left: Vec<String>,
+}
+
+/// Compare open sessions against the current player → server map.
+///
+/// A player on the same server continues their session; a different server
+/// closes it and opens another (`Switch`); absence closes it (`Leave`). Pure,
+/// and players are visited in name order so output is stable.
+pub fn diff_presence(
+    open: &HashMap<String, OpenSession>,
+    current: &HashMap<String, String>,
+    now_ts: i64,
+) -> SessionDiff {
+    let open: BTreeMap<&String, &OpenSession> = open.iter().collect();
+    let current: BTreeMap<&String, &String> = current.iter().collect();
+    let mut diff = SessionDiff::default();
+
+    let end_of = |s: &OpenSession| {
+        if now_ts - s.last_seen_at > SESSION_GAP_SECS {
+            s.last_seen_at
+        } else {
+            now_ts
+        }
+    };
+
+    for (player, session) in &open {
+        let close = |diff: &mut SessionDiff| {
+            diff.closed.push(ClosedSession {
+                player: (*player).clone(),
+                server: session.server.clone(),
+                start: session.started_at,
+                end: end_of(session),
+            });
+        };
+
+        match current.get(player) {
+            None => {
+                close(&mut diff);
+                diff.left.push((*player).clone());
+                diff.events.push(PresenceEvent::Leave {
+                    player: (*player).clone(),
+                    server: session.server.clone(),
+                });
+            }
+            Some(server) if **server != session.server => {
+                close(&mut diff);
+                diff.opened.push(((*player).clone(), (*server).clone()));
+                diff.events.push(PresenceEvent::Switch {
+                    player: (*player).clone(),
+                    from: session.server.clone(),
+                    to: (*server).clone(),
+                });
+            }
+            Some(server) if now_ts - session.last_seen_at > SESSION_GAP_SECS => {
+                // Same server, but we lost sight of them for too long to call it one session
+                close(&mut diff);
+                diff.opened.push(((*player).clone(), (*server).clone()));
+                diff.events.push(PresenceEvent::Leave {
+                    player: (*player).clone(),
+                    server: session.server.clone(),
+                });
+                diff.events.push(PresenceEvent::Join {
+                    player: (*player).clone(),
+                    server: (*server).clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (player, server) in &current {
+        if !open.contains_key(player) {
+            diff.opened.push(((*player).clone(), (*server).clone()));
+            diff.events.push(PresenceEvent::Join {
+                player: (*player).clone(),
+                server: (*server).clone(),
+            });
+        }
+    }
+
+    diff
+}
+
+/// Upsert statement recording a finished session. Keyed on (player, start),
+/// which the unique index enforces, so closing the same session again after a
+/// failed tick is a no-op and keeps the first recorded end.
+pub fn close_statement(s: &ClosedSession) -> Document {
+    doc! {
+        "q": { "player": &s.player, "start": s.start },
+        "u": { "$setOnInsert": {
+            "player": &s.player,
+            "server": &s.server,
+            "start": s.start,
+            "end": s.end,
+            "durationSecs": s.end - s.start,
+            "startAt": BsonDateTime::from_millis(s.start * 1000),
+        }},
+        "upsert": true,
+    }
+}
+
+async fn create_indexes(db: &Database) -> Result<()> {
+    let closed_coll = db.collection::<Document>(CLOSED_COLLECTION);
+
+    let idx = IndexModel::builder()
+        .keys(doc! { "player": 1, "start": -1 })
+        .options(IndexOptions::builder().unique(true).name("player_start_unique".to_string()).build())
+        .build();
+    closed_coll
+        .create_index(idx, None)
+        .await
+        .context("creating player_sessions unique (player, start) index")?;
+    let idx = IndexModel::builder().keys(doc! { "server": 1, "start": -1 }).build();
+    closed_coll
+        .create_index(idx, None)
+        .await
+        .context("creating player_sessions server index")?;
+    Ok(())
+}
+
+/// Advance session tracking by one snapshot and return the presence events.
+///
+/// Sessions are closed before open ones are deleted or restarted; if a later
+/// write fails, the next tick closes the same sessions again, which the
+/// (player, start) upsert absorbs.
+pub async fn track_sessions(
+    db: &Database,
+    current: &HashMap<String, String>,
+    now_ts: i64,
+) -> Result<SessionDiff> {
+    let open_coll = db.collection::<Document>(OPEN_COLLECTION);
+    if let Err(e) = INDEXES_READY.get_or_try_init(|| create_indexes(db)).await {
+        // e.g. duplicate rows written before the unique index existed; the
+        // close upserts stay idempotent without it, and the next tick retries
+        log_error("sessions: creating player_sessions indexes failed", &e, None);
+    }
+
+    let open_docs: Vec<Document> = async {
+        open_coll.find(None, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.find", collection = OPEN_COLLECTION))
+    .await
+    .context("loading open sessions")?;
+
+    let mut open: HashMap<String, OpenSession> = HashMap::with_capacity(open_docs.len());
+    for d in &open_docs {
+        let (Ok(player), Ok(server)) = (d.get_str("_id"), d.get_str("server")) else {
+            continue;
+        };
+        open.insert(
+            player.to_string(),
+            OpenSession {
+                server: server.to_string(),
+                started_at: d.get_i64("startedAt").unwrap_or(now_ts),
+                last_seen_at: d.get_i64("lastSeenAt").unwrap_or(now_ts),
+            },
+        );
+    }
+
+    let diff = diff_presence(&open, current, now_ts);
+
+    if !diff.closed.is_empty() {
+        let closes: Vec<Document> = diff.closed.iter().map(close_statement).collect();
+        let count = closes.len();
+        run_write_command(db, doc! { "update": CLOSED_COLLECTION, "updates": closes, "ordered": true })
+            .instrument(info_span!("mongo.bulk_update", collection = CLOSED_COLLECTION, count))
+            .await
+            .context("recording closed sessions")?;
+    }
+
+    if !diff.left.is_empty() {
+        let count = diff.left.len();
+        run_write_command(
+            db,
+            doc! {
+                "delete": OPEN_COLLECTION,
+                "deletes": [{ "q": { "_id": { "$in": diff.left.clone() } }, "limit": 0 }],
+            },
+        )
+        .instrument(info_span!("mongo.bulk_delete", collection = OPEN_COLLECTION, count))
+        .await
+        .context("closing open sessions")?;
+    }
+
+    // (Re)start sessions, then mark everyone still online as seen now
+    let mut updates: Vec<Document> = diff
+        .opened
+        .iter()
+        .map(|(player, server)| {
+            doc! {
+                "q": { "_id": player },
+                "u": { "$set": { "server": server, "startedAt": now_ts, "lastSeenAt": now_ts } },
+                "upsert": true,
+            }
+        })
+        .collect();
+    if !current.is_empty() {
+        let online: Vec<Bson> = current.keys().cloned().map(Bson::String).collect();
+        updates.push(doc! {
+            "q": { "_id": { "$in": online } },
+            "u": { "$set": { "lastSeenAt": now_ts } },
+            "multi": true,
+        });
+    }
+    if !updates.is_empty() {
+        let count = updates.len();
+        run_write_command(db, doc! { "update": OPEN_COLLECTION, "updates": updates, "ordered": true })
+            .instrument(info_span!("mongo.bulk_update", collection = OPEN_COLLECTION, count))
+            .await
+            .context("updating open sessions")?;
+    }
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn open(entries: &[(&str, &str, i64, i64)]) -> HashMap<String, OpenSession> {
+        entries
+            .iter()
+            .map(|(p, s, started_at, last_seen_at)| {
+                (
+                    p.to_string(),
+                    OpenSession { server: s.to_string(), started_at: *started_at, last_seen_at: *last_seen_at },
+                )
+            })
+            .collect()
+    }
+
+    fn current(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(p, s)| (p.to_string(), s.to_string())).collect()
+    }
+
+    #[test]
+    fn join_leave_and_switch() {
+        let diff = diff_presence(
+            &open(&[("alice", "WC1", NOW - 600, NOW - 35), ("bob", "WC2", NOW - 300, NOW - 35)]),
+            &current(&[("alice", "WC3"), ("carol", "WC1")]),
+            NOW,
+        );
+
+        assert_eq!(
+            diff.events,
+            vec![
+                PresenceEvent::Switch { player: "alice".into(), from: "WC1".into(), to: "WC3".into() },
+                PresenceEvent::Leave { player: "bob".into(), server: "WC2".into() },
+                PresenceEvent::Join { player: "carol".into(), server: "WC1".into() },
+            ]
+        );
+        assert_eq!(
+            diff.closed,
+            vec![
+                ClosedSession { player: "alice".into(), server: "WC1".into(), start: NOW - 600, end: NOW },
+                ClosedSession { player: "bob".into(), server: "WC2".into(), start: NOW - 300, end: NOW },
+            ]
+        );
+        assert_eq!(
+            diff.opened,
+            vec![("alice".into(), "WC3".into()), ("carol".into(), "WC1".into())]
+        );
+        assert_eq!(diff.left, vec!["bob".to_string()]);
+    }
+
+    #[test]
+    fn same_server_continues_session() {
+        let diff = diff_presence(
+            &open(&[("alice", "WC1", NOW - 600, NOW - 35)]),
+            &current(&[("alice", "WC1")]),
+            NOW,
+        );
+        assert_eq!(diff, SessionDiff::default());
+    }
+
+    #[test]
+    fn long_gap_closes_at_last_sighting() {
+        let diff = diff_presence(
+            &open(&[("alice", "WC1", NOW - 7200, NOW - 3600), ("bob", "WC2", NOW - 7200, NOW - 3600)]),
+            &current(&[("alice", "WC1")]),
+            NOW,
+        );
+
+        assert_eq!(
+            diff.closed,
+            vec![
+                ClosedSession { player: "alice".into(), server: "WC1".into(), start: NOW - 7200, end: NOW - 3600 },
+                ClosedSession { player: "bob".into(), server: "WC2".into(), start: NOW - 7200, end: NOW - 3600 },
+            ]
+        );
+        assert_eq!(diff.opened, vec![("alice".into(), "WC1".into())]);
+    }
+
+    #[test]
+    fn closing_again_keeps_the_first_end() {
+        let first = ClosedSession { player: "alice".into(), server: "WC1".into(), start: NOW - 600, end: NOW };
+        let retried = ClosedSession { end: NOW + 30, ..first.clone() };
+        let (a, b) = (close_statement(&first), close_statement(&retried));
+
+        // Same key, so the retry matches the stored row; nothing outside $setOnInsert
+        assert_eq!(a.get("q"), b.get("q"));
+        assert_eq!(a.get_document("q").unwrap(), &doc! { "player": "alice", "start": NOW - 600 });
+        let update = a.get_document("u").unwrap();
+        assert_eq!(update.keys().collect::<Vec<_>>(), vec!["$setOnInsert"]);
+        assert_eq!(update.get_document("$setOnInsert").unwrap().get_i64("durationSecs").unwrap(), 600);
+    }
+}
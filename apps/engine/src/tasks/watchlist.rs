@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    Database, IndexModel,
+};
+use serde_json::json;
+use tracing::{info_span, Instrument};
+
+use crate::config::WATCHLIST_CHANNEL;
+use crate::logger::log_error;
+use crate::redis_client::publish_json;
+use crate::tasks::sessions::PresenceEvent;
+
+/// Players to follow, one doc per player with a `player` name field.
+const WATCHLIST_COLLECTION: &str = "player_watchlist";
+/// Presence changes of watched players.
+const EVENTS_COLLECTION: &str = "player_presence_events";
+
+/// The events concerning watched players. Names compare case-insensitively,
+/// like Minecraft usernames.
+pub fn watched_events<'a>(events: &'a [PresenceEvent], watched: &HashSet<String>) -> Vec<&'a PresenceEvent> {
+    events
+        .iter()
+        .filter(|e| watched.contains(&e.player().to_lowercase()))
+        .collect()
+}
+
+/// Event fields shared by the stored doc and the pub/sub payload.
+fn event_fields(event: &PresenceEvent) -> (Option<&str>, Option<&str>) {
+    match event {
+        PresenceEvent::Join { server, .. } => (None, Some(server)),
+        PresenceEvent::Leave { server, .. } => (Some(server), None),
+        PresenceEvent::Switch { from, to, .. } => (Some(from), Some(to)),
+    }
+}
+
+/// Store and publish this tick's presence events for players on the watchlist.
+/// Returns how many events concerned watched players.
+pub async fn notify_watchlist(db: &Database, events: &[PresenceEvent], now_ts: i64) -> Result<usize> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    let watch_docs: Vec<Document> = async {
+        db.collection::<Document>(WATCHLIST_COLLECTION)
+            .find(None, None)
+            .await?
+            .try_collect()
+            .await
+    }
+    .instrument(info_span!("mongo.find", collection = WATCHLIST_COLLECTION))
+    .await
+    .context("loading player watchlist")?;
+    let watched: HashSet<String> = watch_docs
+        .iter()
+        .filter_map(|d| d.get_str("player").ok())
+        .map(str::to_lowercase)
+        .collect();
+
+    let matched = watched_events(events, &watched);
+    if matched.is_empty() {
+        return Ok(0);
+    }
+
+    let events_coll = db.collection::<Document>(EVENTS_COLLECTION);
+    let idx = IndexModel::builder().keys(doc! { "player": 1, "at": -1 }).build();
+    let _ = events_coll
+        .create_index(idx, None)
+        .await
+        .context("creating player_presence_events index")?;
+
+    let at = BsonDateTime::from_millis(now_ts * 1000);
+    let docs: Vec<Document> = matched
+        .iter()
+        .map(|e| {
+            let (from, to) = event_fields(e);
+            doc! {
+                "player": e.player(),
+                "event": e.kind(),
+                "from": from.map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null),
+                "to": to.map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null),
+                "at": at,
+            }
+        })
+        .collect();
+    let count = docs.len();
+    events_coll
+        .insert_many(docs, None)
+        .instrument(info_span!("mongo.insert_many", collection = EVENTS_COLLECTION, count))
+        .await
+        .context("inserting player presence events")?;
+
+    // Stored events are the record; a missed live notification is only logged
+    for e in &matched {
+        let (from, to) = event_fields(e);
+        let payload = json!({
+            "player": e.player(),
+            "event": e.kind(),
+            "from": from,
+            "to": to,
+            "at": now_ts,
+        });
+        if let Err(err) = publish_json(&WATCHLIST_CHANNEL, payload).await {
+            log_error("watchlist: publishing presence event failed", &err, None);
+            break;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_watched_players_case_insensitively() {
+        let events = vec![
+            PresenceEvent::Join { player: "Alice".into(), server: "WC1".into() },
+            PresenceEvent::Leave { player: "bob".into(), server: "WC2".into() },
+            PresenceEvent::Switch { player: "carol".into(), from: "WC1".into(), to: "WC3".into() },
+        ];
+        let watched: HashSet<String> = HashSet::from(["alice".to_string(), "carol".to_string()]);
+
+        let matched: Vec<&str> = watched_events(&events, &watched).iter().map(|e| e.player()).collect();
+        assert_eq!(matched, vec!["Alice", "carol"]);
+    }
+}
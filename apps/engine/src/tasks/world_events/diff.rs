@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use mongodb::bson::{doc, Bson, Document};
+
+/// Static fields compared between the stored and the fetched event.
+const DIFFED_FIELDS: &[&str] = &[
+    "name", "lore", "difficulty", "level", "length",
+    "rewardPerLevel", "requirements", "location",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Add,
+    Remove,
+    Change,
+}
+
+impl ChangeOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeOp::Add => "add",
+            ChangeOp::Remove => "remove",
+            ChangeOp::Change => "change",
+        }
+    }
+}
+
+/// One leaf-level difference between two event docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// JSON-Pointer-style path, e.g. `/rewardPerLevel/10/2`. Elements of keyed
+    /// arrays are addressed by key instead of index: `/requirements/level/value`.
+    pub path: String,
+    pub op: ChangeOp,
+    pub before: Option<Bson>,
+    pub after: Option<Bson>,
+}
+
+impl FieldChange {
+    /// The top-level field the change falls under.
+    pub fn field(&self) -> &str {
+        self.path.trim_start_matches('/').split('/').next().unwrap_or("")
+    }
+
+    pub fn to_bson(&self) -> Bson {
+        Bson::Document(doc! {
+            "field": self.field(),
+            "path": &self.path,
+            "op": self.op.as_str(),
+            "before": self.before.clone().unwrap_or(Bson::Null),
+            "after": self.after.clone().unwrap_or(Bson::Null),
+        })
+    }
+}
+
+/// Compare old and new static event documents field by field, down to leaves.
+pub fn diff_event_docs(old: &Document, new: &Document) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    for field in DIFFED_FIELDS {
+        let path = format!("/{}", escape(field));
+        diff_value(&path, field, old.get(*field), new.get(*field), &mut changes);
+    }
+    changes
+}
+
+/// RFC 6901 escaping of a single path segment.
+fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn diff_value(path: &str, field: &str, old: Option<&Bson>, new: Option<&Bson>, out: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (None, None) => {}
+        (None, Some(b)) => out.push(FieldChange {
+            path: path.to_string(),
+            op: ChangeOp::Add,
+            before: None,
+            after: Some(b.clone()),
+        }),
+        (Some(a), None) => out.push(FieldChange {
+            path: path.to_string(),
+            op: ChangeOp::Remove,
+            before: Some(a.clone()),
+            after: None,
+        }),
+        (Some(Bson::Document(a)), Some(Bson::Document(b))) => {
+            for (key, va) in a {
+                diff_value(&format!("{path}/{}", escape(key)), field, Some(va), b.get(key), out);
+            }
+            for (key, vb) in b {
+                if !a.contains_key(key) {
+                    diff_value(&format!("{path}/{}", escape(key)), field, None, Some(vb), out);
+                }
+            }
+        }
+        (Some(Bson::Array(a)), Some(Bson::Array(b))) => match (keyed(field, a), keyed(field, b)) {
+            (Some(ka), Some(kb)) => {
+                for (key, va) in &ka {
+                    let vb = kb.iter().find(|(k, _)| k == key).map(|(_, v)| *v);
+                    diff_value(&format!("{path}/{}", escape(key)), field, Some(*va), vb, out);
+                }
+                for (key, vb) in &kb {
+                    if !ka.iter().any(|(k, _)| k == key) {
+                        diff_value(&format!("{path}/{}", escape(key)), field, None, Some(*vb), out);
+                    }
+                }
+            }
+            _ => {
+                for i in 0..a.len().max(b.len()) {
+                    diff_value(&format!("{path}/{i}"), field, a.get(i), b.get(i), out);
+                }
+            }
+        },
+        (Some(a), Some(b)) => {
+            if !scalars_equal(a, b) {
+                out.push(FieldChange {
+                    path: path.to_string(),
+                    op: ChangeOp::Change,
+                    before: Some(a.clone()),
+                    after: Some(b.clone()),
+                });
+            }
+        }
+    }
+}
+
+/// Key the elements of `location` (by event coordinates) and `requirements`
+/// (by type) so reordering or inserting one does not show up as every
+/// following element changing. `None` when the field is not keyed or keys
+/// are missing or repeated; the caller then compares by index.
+fn keyed<'a>(field: &str, items: &'a [Bson]) -> Option<Vec<(String, &'a Bson)>> {
+    let key_of = |item: &Bson| -> Option<String> {
+        let d = item.as_document()?;
+        match field {
+            "requirements" => d.get_str("type").ok().map(str::to_string),
+            "location" => {
+                let c = d.get_document("event").ok()?;
+                let coord = |k: &str| c.get(k).and_then(bson_i64);
+                Some(format!("{},{},{}", coord("x")?, coord("y")?, coord("z")?))
+            }
+            _ => None,
+        }
+    };
+
+    let mut seen: HashSet<String> = HashSet::with_capacity(items.len());
+    let mut keyed = Vec::with_capacity(items.len());
+    for item in items {
+        let key = key_of(item)?;
+        if !seen.insert(key.clone()) {
+            return None;
+        }
+        keyed.push((key, item));
+    }
+    Some(keyed)
+}
+
+fn bson_i64(v: &Bson) -> Option<i64> {
+    match v {
+        Bson::Int32(i) => Some(*i as i64),
+        Bson::Int64(i) => Some(*i),
+        _ => None,
+    }
+}
+
+/// Leaf equality, treating Int32 and Int64 of the same value as equal.
+fn scalars_equal(a: &Bson, b: &Bson) -> bool {
+    match (bson_i64(a), bson_i64(b)) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(changes: &[FieldChange]) -> Vec<(&str, ChangeOp)> {
+        changes.iter().map(|c| (c.path.as_str(), c.op)).collect()
+    }
+
+    #[test]
+    fn one_reward_change_is_one_entry() {
+        let old = doc! { "rewardPerLevel": { "10": ["Emerald", "Potion"], "20": ["Key"] } };
+        let new = doc! { "rewardPerLevel": { "10": ["Emerald", "Scroll"], "20": ["Key"] } };
+
+        let changes = diff_event_docs(&old, &new);
+        assert_eq!(
+            changes,
+            vec![FieldChange {
+                path: "/rewardPerLevel/10/1".into(),
+                op: ChangeOp::Change,
+                before: Some(Bson::String("Potion".into())),
+                after: Some(Bson::String("Scroll".into())),
+            }]
+        );
+        assert_eq!(changes[0].field(), "rewardPerLevel");
+    }
+
+    #[test]
+    fn added_keys_and_elements_are_leaf_adds() {
+        let old = doc! { "level": 50_i64, "rewardPerLevel": { "10": ["A"] } };
+        let new = doc! { "level": 50_i32, "rewardPerLevel": { "10": ["A", "B"], "30": ["C"] }, "length": "10m" };
+
+        assert_eq!(
+            paths(&diff_event_docs(&old, &new)),
+            vec![
+                ("/length", ChangeOp::Add),
+                ("/rewardPerLevel/10/1", ChangeOp::Add),
+                ("/rewardPerLevel/30", ChangeOp::Add),
+            ]
+        );
+    }
+
+    #[test]
+    fn requirements_match_by_type() {
+        let old = doc! { "requirements": [
+            { "type": "level", "value": 50_i64 },
+            { "type": "quest", "value": "Wynn Guide" },
+        ]};
+        let new = doc! { "requirements": [
+            { "type": "quest", "value": "Wynn Guide" },
+            { "type": "level", "value": 60_i64 },
+        ]};
+
+        assert_eq!(paths(&diff_event_docs(&old, &new)), vec![("/requirements/level/value", ChangeOp::Change)]);
+    }
+
+    #[test]
+    fn locations_match_by_event_coordinates() {
+        let loc = |x: i64, radius: i64| doc! { "event": { "x": x, "y": 64_i64, "z": -300_i64 }, "radius": radius };
+        let old = doc! { "location": [loc(100, 20), loc(200, 20)] };
+        let new = doc! { "location": [loc(200, 25), loc(300, 20)] };
+
+        assert_eq!(
+            paths(&diff_event_docs(&old, &new)),
+            vec![
+                ("/location/100,64,-300", ChangeOp::Remove),
+                ("/location/200,64,-300/radius", ChangeOp::Change),
+                ("/location/300,64,-300", ChangeOp::Add),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_segments_are_escaped() {
+        let old = doc! { "rewardPerLevel": { "1/2": ["A"] } };
+        let new = doc! { "rewardPerLevel": { "1/2": ["B"] } };
+
+        assert_eq!(diff_event_docs(&old, &new)[0].path, "/rewardPerLevel/1~12/0");
+    }
+}
@@ -0,0 +1,317 @@
+mod diff;
+mod model;
+mod notify;
+pub mod schedule;
+mod spatial;
+mod sync;
+mod timeline;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde_json::Value;
+use tracing::{info_span, Instrument};
+
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::IndexOptions,
+    IndexModel,
+};
+
+use crate::logger::{log_error, log_event};
+use crate::mongo_client::{database, run_write_command};
+use crate::scheduler::failure::UnexpectedPayload;
+use crate::scheduler::node::{TaskFuture, TaskSummary};
+use wynnpool_engine_macros::fetch;
+
+use model::{parse_requirements, parse_rewards, UnknownShape};
+use notify::{publish_notifications, NotifyFilter};
+use schedule::{parse_schedule_to_ms, snapshot_statement, SCHEDULES_COLLECTION};
+use spatial::{annotate_locations, ensure_spatial_index, Territory};
+use sync::{reconcile_events, FetchedEvent};
+use timeline::update_timeline;
+
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+const WORLD_EVENTS_URL: &str = "https://api.wynncraft.com/v3/map/world-events";
+
+#[fetch(interval = 120)]
+fn update_world_events() -> TaskFuture {
+    Box::pin(run_update_world_events())
+}
+
+async fn run_update_world_events() -> Result<TaskSummary> {
+    let whole_start = Instant::now();
+    log_event("TASK", "fetching world events", None);
+
+    // --- 1. HTTP FETCH ---
+    let http_start = Instant::now();
+    let resp: Value = async {
+        CLIENT
+            .get(WORLD_EVENTS_URL)
+            .send()
+            .await
+            .context("requesting /v3/map/world-events")?
+            .error_for_status()
+            .context("requesting /v3/map/world-events")?
+            .json()
+            .await
+            .context("decoding /v3/map/world-events response")
+    }
+    .instrument(info_span!("http.fetch", url = WORLD_EVENTS_URL))
+    .await?;
+    let http_elapsed = http_start.elapsed();
+
+    let events = resp
+        .as_array()
+        .ok_or(UnexpectedPayload("Expected JSON array from world events API"))?;
+
+    // --- 2. MONGODB ---
+    let mongo_start = Instant::now();
+
+    let db = database().await?;
+
+    let events_coll = db.collection::<Document>("world_events");
+    let schedules_coll = db.collection::<Document>(SCHEDULES_COLLECTION);
+    let changelog_coll = db.collection::<Document>("world_event_changelog");
+
+    // Ensure TTL index on schedules collection; `expireAt` moves forward while
+    // a schedule value is still being observed.
+    let idx = IndexModel::builder()
+        .keys(doc! { "expireAt": 1 })
+        .options(
+            IndexOptions::builder()
+                .expire_after(Some(std::time::Duration::from_secs(0)))
+                .build(),
+        )
+        .build();
+    let _ = schedules_coll
+        .create_index(idx, None)
+        .await
+        .context("creating world_event_schedules TTL index")?;
+    // Not unique: snapshots written before deduplication may repeat a value
+    let idx = IndexModel::builder().keys(doc! { "internalName": 1, "schedule": 1 }).build();
+    let _ = schedules_coll
+        .create_index(idx, None)
+        .await
+        .context("creating world_event_schedules lookup index")?;
+    let idx = IndexModel::builder().keys(doc! { "internalName": 1, "lastObservedAt": -1 }).build();
+    let _ = schedules_coll
+        .create_index(idx, None)
+        .await
+        .context("creating world_event_schedules recency index")?;
+    ensure_spatial_index(&events_coll).await?;
+
+    let now_ts: i64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    // --- 3. Load every stored event in one query ---
+    let existing_docs: Vec<Document> = async {
+        events_coll.find(None, None).await?.try_collect().await
+    }
+    .instrument(info_span!("mongo.find", collection = "world_events"))
+    .await
+    .context("loading world events")?;
+    let existing: HashMap<String, Document> = existing_docs
+        .into_iter()
+        .filter_map(|d| Some((d.get_str("internalName").ok()?.to_string(), d)))
+        .collect();
+
+    // --- 4. Build static event docs + schedule entries ---
+    let territories = spatial::territories(&CLIENT).await;
+    let mut schedule_docs: Vec<Document> = Vec::with_capacity(events.len());
+    let mut fetched: Vec<FetchedEvent> = Vec::with_capacity(events.len());
+    let mut static_docs: HashMap<String, Document> = HashMap::with_capacity(events.len());
+    let mut observed: BTreeMap<String, i64> = BTreeMap::new();
+
+    for event in events {
+        let internal_name = event["internalName"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        if internal_name.is_empty() {
+            continue;
+        }
+
+        // --- 4a. Schedule snapshot ---
+        schedule_docs.push(snapshot_statement(&internal_name, event["schedule"].as_str(), now_ts));
+        if let Some(start_ms) = event["schedule"].as_str().and_then(parse_schedule_to_ms) {
+            observed.insert(internal_name.clone(), start_ms);
+        }
+
+        // --- 4b. Static event data ---
+        let (new_doc, unknown) = build_static_event_doc(event, &territories);
+        report_unknown_shapes(&internal_name, &unknown);
+        static_docs.insert(internal_name.clone(), new_doc.clone());
+        fetched.push(FetchedEvent { internal_name, doc: new_doc });
+    }
+
+    // --- 5. Diff in memory, then apply with one update and one insert ---
+    let writes = reconcile_events(&fetched, &existing, now_ts);
+
+    if !writes.updates.is_empty() {
+        let count = writes.updates.len();
+        run_write_command(&db, doc! { "update": "world_events", "updates": &writes.updates, "ordered": true })
+            .instrument(info_span!("mongo.bulk_update", collection = "world_events", count))
+            .await
+            .context("writing world events")?;
+    }
+
+    if !writes.changelog.is_empty() {
+        let count = writes.changelog.len();
+        changelog_coll
+            .insert_many(&writes.changelog, None)
+            .instrument(info_span!("mongo.insert_many", collection = "world_event_changelog", count))
+            .await
+            .context("writing world event changelog")?;
+    }
+
+    // Notifications are best-effort; the changelog above is the record
+    let notified = match publish_notifications(&writes.changelog, &NotifyFilter::from_config()).await {
+        Ok(n) => n,
+        Err(err) => {
+            log_error("world events: publishing change notifications failed", &err, None);
+            0
+        }
+    };
+
+    // --- 6. Upsert schedule snapshots; only new schedule values insert a doc ---
+    let mut new_schedules = 0usize;
+    if !schedule_docs.is_empty() {
+        let count = schedule_docs.len();
+        let reply = run_write_command(
+            &db,
+            doc! { "update": SCHEDULES_COLLECTION, "updates": schedule_docs, "ordered": false },
+        )
+        .instrument(info_span!("mongo.bulk_update", collection = SCHEDULES_COLLECTION, count))
+        .await
+        .context("upserting schedule snapshots")?;
+        new_schedules = reply.get_array("upserted").map(|u| u.len()).unwrap_or(0);
+    }
+
+    // --- 7. Upcoming occurrences, observed and predicted ---
+    let timeline_count = update_timeline(&db, &static_docs, &observed, now_ts * 1000).await?;
+
+    let mongo_elapsed = mongo_start.elapsed();
+    let whole_elapsed = whole_start.elapsed();
+
+    log_event(
+        "SUMMARY",
+        &format!(
+            "world events: total={}, static: added={} changed={} unchanged={} removed={} reactivated={} notified={} | new schedules={} timeline={} (http={}ms, mongo={}ms)",
+            events.len(),
+            writes.added,
+            writes.changed,
+            writes.unchanged,
+            writes.removed,
+            writes.reactivated,
+            notified,
+            new_schedules,
+            timeline_count,
+            http_elapsed.as_millis(),
+            mongo_elapsed.as_millis(),
+        ),
+        Some(whole_elapsed),
+    );
+
+    Ok(doc! {
+        "events": events.len() as i64,
+        "added": writes.added as i64,
+        "changed": writes.changed as i64,
+        "unchanged": writes.unchanged as i64,
+        "removed": writes.removed as i64,
+        "reactivated": writes.reactivated as i64,
+        "notified": notified as i64,
+        "newSchedules": new_schedules as i64,
+        "timeline": timeline_count as i64,
+    })
+}
+
+/// Build a MongoDB document for static event data (everything except schedule),
+/// along with any reward/requirement shapes that were kept raw.
+fn build_static_event_doc(event: &Value, territories: &[Territory]) -> (Document, Vec<UnknownShape>) {
+    let name = event["name"].as_str().unwrap_or("");
+    let internal_name = event["internalName"].as_str().unwrap_or("");
+    let lore = event["lore"].as_str().unwrap_or("");
+    let difficulty = event["difficulty"].as_str().map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null);
+    let level = event["level"].as_i64().map(Bson::Int64).unwrap_or(Bson::Null);
+    let length = event["length"].as_str().map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null);
+
+    let mut unknown = Vec::new();
+    let reward_per_level = parse_rewards(&event["rewardPerLevel"], &mut unknown);
+    let requirements = parse_requirements(&event["requirements"], &mut unknown);
+
+    let location = if let Some(arr) = event["location"].as_array() {
+        let locs: Vec<Bson> = arr
+            .iter()
+            .map(|loc| {
+                let mut d = Document::new();
+                for field in &["event", "spawn", "reward"] {
+                    if let Some(coord) = loc[*field].as_object() {
+                        let mut cd = Document::new();
+                        if let Some(x) = coord["x"].as_i64() {
+                            cd.insert("x", Bson::Int64(x));
+                        }
+                        if let Some(y) = coord["y"].as_i64() {
+                            cd.insert("y", Bson::Int64(y));
+                        }
+                        if let Some(z) = coord["z"].as_i64() {
+                            cd.insert("z", Bson::Int64(z));
+                        }
+                        d.insert(*field, Bson::Document(cd));
+                    } else {
+                        d.insert(*field, Bson::Null);
+                    }
+                }
+                if let Some(r) = loc["radius"].as_i64() {
+                    d.insert("radius", Bson::Int64(r));
+                }
+                if let Some(sr) = loc["spawnRadius"].as_i64() {
+                    d.insert("spawnRadius", Bson::Int64(sr));
+                }
+                Bson::Document(d)
+            })
+            .collect();
+        Bson::Array(locs)
+    } else {
+        Bson::Array(vec![])
+    };
+
+    let mut doc = doc! {
+        "name": name,
+        "internalName": internal_name,
+        "lore": lore,
+        "difficulty": difficulty,
+        "level": level,
+        "length": length,
+        "rewardPerLevel": reward_per_level,
+        "requirements": requirements,
+        "location": location,
+    };
+    annotate_locations(&mut doc, territories);
+    (doc, unknown)
+}
+
+/// Log each unmodelled shape once per process rather than every poll.
+fn report_unknown_shapes(internal_name: &str, unknown: &[UnknownShape]) {
+    static REPORTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+    let mut reported = REPORTED.lock().unwrap();
+    for shape in unknown {
+        if reported.insert(format!("{internal_name}{}", shape.path)) {
+            log_event(
+                "WARN",
+                &format!("world events: {internal_name}{} has an unrecognised shape, stored raw: {}", shape.path, shape.value),
+                None,
+            );
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use mongodb::bson::{doc, Bson, Document};
+use serde_json::Value;
+
+/// A shape the typed models do not recognise. The value is stored as-is; the
+/// issue is reported so the model can be extended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownShape {
+    /// JSON-Pointer-style location within the event, e.g. `/rewardPerLevel/10/2`.
+    pub path: String,
+    pub value: Value,
+}
+
+/// One reward entry of a `rewardPerLevel` tier.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reward {
+    /// An item reference, from `"64x Liquid Emerald"`, `"Corkian Insulator"` or
+    /// an object with a name and optional amount. `text` keeps the upstream
+    /// string verbatim when there was one.
+    Item {
+        name: String,
+        quantity: Option<i64>,
+        text: Option<String>,
+    },
+    Raw(Value),
+}
+
+impl Reward {
+    pub fn parse(value: &Value) -> Reward {
+        match value {
+            Value::String(s) => {
+                let (quantity, name) = split_quantity(s);
+                Reward::Item { name, quantity, text: Some(s.clone()) }
+            }
+            Value::Object(o) => {
+                let name = ["name", "item", "itemName"].iter().find_map(|k| o.get(*k)?.as_str());
+                let quantity = ["amount", "quantity", "count"].iter().find_map(|k| o.get(*k)?.as_i64());
+                let known = ["name", "item", "itemName", "amount", "quantity", "count"];
+                match name {
+                    // Only claim the object when nothing in it would be lost
+                    Some(name) if o.keys().all(|k| known.contains(&k.as_str())) => Reward::Item {
+                        name: name.to_string(),
+                        quantity,
+                        text: None,
+                    },
+                    _ => Reward::Raw(value.clone()),
+                }
+            }
+            _ => Reward::Raw(value.clone()),
+        }
+    }
+
+    pub fn to_bson(&self) -> Bson {
+        match self {
+            Reward::Item { name, quantity, text } => Bson::Document(doc! {
+                "kind": "item",
+                "name": name,
+                "quantity": quantity.map(Bson::Int64).unwrap_or(Bson::Null),
+                "text": text.clone().map(Bson::String).unwrap_or(Bson::Null),
+            }),
+            Reward::Raw(v) => Bson::Document(doc! { "kind": "raw", "value": json_to_bson(v) }),
+        }
+    }
+}
+
+/// `"64x Liquid Emerald"` / `"64 Liquid Emerald"` → (64, "Liquid Emerald").
+fn split_quantity(s: &str) -> (Option<i64>, String) {
+    let trimmed = s.trim();
+    if let Some((head, rest)) = trimmed.split_once(' ') {
+        let digits = head.strip_suffix(['x', 'X']).unwrap_or(head);
+        if let Ok(n) = digits.parse::<i64>() {
+            return (Some(n), rest.trim().to_string());
+        }
+    }
+    (None, trimmed.to_string())
+}
+
+/// One entry of an event's `requirements`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// Minimum combat level. `type_name` is the upstream `type` as written.
+    Level { type_name: String, min: i64 },
+    /// A quest that must be completed.
+    Quest { type_name: String, name: String },
+    /// A typed requirement we have no model for; the value is kept verbatim.
+    Other { kind: String, value: Value },
+    Raw(Value),
+}
+
+impl Requirement {
+    pub fn parse(value: &Value) -> Requirement {
+        let Some(kind) = value["type"].as_str() else {
+            return Requirement::Raw(value.clone());
+        };
+        let extra_keys = value.as_object().is_some_and(|o| o.keys().any(|k| k != "type" && k != "value"));
+        if extra_keys {
+            return Requirement::Raw(value.clone());
+        }
+        match (kind.to_ascii_lowercase().as_str(), &value["value"]) {
+            ("level", v) if v.as_i64().is_some() => Requirement::Level {
+                type_name: kind.to_string(),
+                min: v.as_i64().unwrap_or(0),
+            },
+            ("quest", Value::String(name)) => Requirement::Quest { type_name: kind.to_string(), name: name.clone() },
+            (_, v) => Requirement::Other { kind: kind.to_string(), value: v.clone() },
+        }
+    }
+
+    /// Stored with the upstream `type`/`value` pair intact, so the changelog's
+    /// key matching and existing readers keep working.
+    pub fn to_bson(&self) -> Bson {
+        match self {
+            Requirement::Level { type_name, min } => Bson::Document(doc! {
+                "type": type_name,
+                "value": *min,
+                "kind": "level",
+                "minLevel": *min,
+            }),
+            Requirement::Quest { type_name, name } => Bson::Document(doc! {
+                "type": type_name,
+                "value": name,
+                "kind": "quest",
+                "quest": name,
+            }),
+            Requirement::Other { kind, value } => Bson::Document(doc! {
+                "type": kind,
+                "value": json_to_bson(value),
+                "kind": "other",
+            }),
+            Requirement::Raw(v) => Bson::Document(doc! { "kind": "raw", "value": json_to_bson(v) }),
+        }
+    }
+}
+
+/// Lossless JSON → BSON; integers stay integers, fractions stay doubles.
+fn json_to_bson(value: &Value) -> Bson {
+    mongodb::bson::to_bson(value).unwrap_or(Bson::Null)
+}
+
+/// Typed `rewardPerLevel`: the upstream tier keys, each with typed rewards.
+/// A tier that is not a list is kept raw.
+pub fn parse_rewards(value: &Value, unknown: &mut Vec<UnknownShape>) -> Bson {
+    let Some(tiers) = value.as_object() else {
+        if !value.is_null() {
+            unknown.push(UnknownShape { path: "/rewardPerLevel".into(), value: value.clone() });
+            return Bson::Document(doc! { "kind": "raw", "value": json_to_bson(value) });
+        }
+        return Bson::Null;
+    };
+
+    let mut out = Document::new();
+    for (tier, rewards) in tiers {
+        let Some(items) = rewards.as_array() else {
+            unknown.push(UnknownShape { path: format!("/rewardPerLevel/{tier}"), value: rewards.clone() });
+            out.insert(tier.clone(), doc! { "kind": "raw", "value": json_to_bson(rewards) });
+            continue;
+        };
+        let parsed: Vec<Bson> = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let reward = Reward::parse(item);
+                if let Reward::Raw(v) = &reward {
+                    unknown.push(UnknownShape { path: format!("/rewardPerLevel/{tier}/{i}"), value: v.clone() });
+                }
+                reward.to_bson()
+            })
+            .collect();
+        out.insert(tier.clone(), Bson::Array(parsed));
+    }
+    Bson::Document(out)
+}
+
+/// Typed `requirements` list.
+pub fn parse_requirements(value: &Value, unknown: &mut Vec<UnknownShape>) -> Bson {
+    let Some(items) = value.as_array() else {
+        if !value.is_null() {
+            unknown.push(UnknownShape { path: "/requirements".into(), value: value.clone() });
+            return Bson::Document(doc! { "kind": "raw", "value": json_to_bson(value) });
+        }
+        return Bson::Null;
+    };
+
+    let parsed: Vec<Bson> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let requirement = Requirement::parse(item);
+            // Raw shapes and unmodelled types are both kept verbatim; report either
+            if matches!(requirement, Requirement::Raw(_) | Requirement::Other { .. }) {
+                unknown.push(UnknownShape { path: format!("/requirements/{i}"), value: item.clone() });
+            }
+            requirement.to_bson()
+        })
+        .collect();
+    Bson::Array(parsed)
+}
+
+/// Bring a stored event doc written before the typed models up to date:
+/// plain reward strings and requirements without a `kind` are parsed the way a
+/// fresh fetch would be. Comparing the upgraded doc keeps the change of
+/// representation out of the changelog. Typed entries are left as they are.
+pub fn upgrade_legacy(stored: &Document) -> Document {
+    let mut doc = stored.clone();
+
+    if let Ok(tiers) = doc.get_document_mut("rewardPerLevel") {
+        for (_, rewards) in tiers.iter_mut() {
+            let Bson::Array(items) = rewards else {
+                continue;
+            };
+            for item in items.iter_mut() {
+                if let Bson::String(text) = item {
+                    *item = Reward::parse(&Value::String(text.clone())).to_bson();
+                }
+            }
+        }
+    }
+
+    if let Ok(items) = doc.get_array_mut("requirements") {
+        for item in items.iter_mut() {
+            let legacy = item.as_document().is_some_and(|d| !d.contains_key("kind"));
+            if legacy {
+                *item = Requirement::parse(&item.clone().into_relaxed_extjson()).to_bson();
+            }
+        }
+    }
+
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reward_strings_become_item_references() {
+        assert_eq!(
+            Reward::parse(&json!("64x Liquid Emerald")),
+            Reward::Item { name: "Liquid Emerald".into(), quantity: Some(64), text: Some("64x Liquid Emerald".into()) }
+        );
+        assert_eq!(
+            Reward::parse(&json!("Corkian Insulator")),
+            Reward::Item { name: "Corkian Insulator".into(), quantity: None, text: Some("Corkian Insulator".into()) }
+        );
+        assert_eq!(
+            Reward::parse(&json!({ "name": "Emerald Block", "amount": 3 })),
+            Reward::Item { name: "Emerald Block".into(), quantity: Some(3), text: None }
+        );
+    }
+
+    #[test]
+    fn unrecognised_rewards_are_kept_raw_and_reported() {
+        let mut unknown = Vec::new();
+        let rewards = parse_rewards(
+            &json!({ "10": ["Potion", 5, { "name": "Key", "tier": 2 }], "20": "Scroll" }),
+            &mut unknown,
+        );
+
+        let paths: Vec<&str> = unknown.iter().map(|u| u.path.as_str()).collect();
+        assert_eq!(paths, vec!["/rewardPerLevel/10/1", "/rewardPerLevel/10/2", "/rewardPerLevel/20"]);
+
+        let tier = rewards.as_document().unwrap().get_array("10").unwrap();
+        assert_eq!(tier[1].as_document().unwrap().get_i64("value").unwrap(), 5);
+        assert_eq!(
+            tier[2].as_document().unwrap().get_document("value").unwrap().get_i64("tier").unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn requirements_keep_non_integer_values() {
+        let mut unknown = Vec::new();
+        let reqs = parse_requirements(
+            &json!([
+                { "type": "level", "value": 80 },
+                { "type": "quest", "value": "The Realm of Light" },
+                { "type": "guildLevel", "value": 2.5 },
+                { "value": 1 },
+            ]),
+            &mut unknown,
+        );
+        let reqs = reqs.as_array().unwrap();
+
+        assert_eq!(reqs[0].as_document().unwrap().get_i64("minLevel").unwrap(), 80);
+        assert_eq!(reqs[1].as_document().unwrap().get_str("quest").unwrap(), "The Realm of Light");
+        assert_eq!(reqs[2].as_document().unwrap().get_f64("value").unwrap(), 2.5);
+        assert_eq!(reqs[3].as_document().unwrap().get_str("kind").unwrap(), "raw");
+        let paths: Vec<&str> = unknown.iter().map(|u| u.path.as_str()).collect();
+        assert_eq!(paths, vec!["/requirements/2", "/requirements/3"]);
+    }
+
+    #[test]
+    fn unmodelled_requirements_are_reported() {
+        let mut unknown = Vec::new();
+        let reqs = parse_requirements(
+            &json!([
+                { "type": "level", "value": "80+" },
+                { "type": "profession", "value": { "mining": 30 } },
+                { "type": "level", "value": 80 },
+            ]),
+            &mut unknown,
+        );
+        let reqs = reqs.as_array().unwrap();
+
+        assert_eq!(reqs[0].as_document().unwrap().get_str("kind").unwrap(), "other");
+        assert_eq!(reqs[1].as_document().unwrap().get_str("kind").unwrap(), "other");
+        let paths: Vec<&str> = unknown.iter().map(|u| u.path.as_str()).collect();
+        assert_eq!(paths, vec!["/requirements/0", "/requirements/1"]);
+        assert_eq!(unknown[1].value, json!({ "type": "profession", "value": { "mining": 30 } }));
+    }
+
+    #[test]
+    fn requirement_type_is_kept_as_written() {
+        let reqs = parse_requirements(&json!([{ "type": "Level", "value": 80 }]), &mut Vec::new());
+        let req = reqs.as_array().unwrap()[0].as_document().unwrap().clone();
+
+        assert_eq!(req.get_str("type").unwrap(), "Level");
+        assert_eq!(req.get_str("kind").unwrap(), "level");
+    }
+
+    #[test]
+    fn legacy_docs_upgrade_to_what_a_fetch_builds() {
+        let legacy = doc! {
+            "rewardPerLevel": { "10": ["64x Liquid Emerald"] },
+            "requirements": [{ "type": "level", "value": 80_i64 }, { "type": "quest", "value": "Wynn Guide" }],
+        };
+        let event = json!({
+            "rewardPerLevel": { "10": ["64x Liquid Emerald"] },
+            "requirements": [{ "type": "level", "value": 80 }, { "type": "quest", "value": "Wynn Guide" }],
+        });
+        let fetched = doc! {
+            "rewardPerLevel": parse_rewards(&event["rewardPerLevel"], &mut Vec::new()),
+            "requirements": parse_requirements(&event["requirements"], &mut Vec::new()),
+        };
+
+        assert_eq!(upgrade_legacy(&legacy), fetched);
+        // Already typed docs come back unchanged
+        assert_eq!(upgrade_legacy(&fetched), fetched);
+    }
+}
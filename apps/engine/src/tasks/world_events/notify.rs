@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use mongodb::bson::{Bson, Document};
+
+use crate::config::{WORLD_EVENT_NOTIFY_FIELDS, WORLD_EVENT_STREAM, WORLD_EVENT_STREAM_MAXLEN};
+use crate::redis_client::stream_add;
+
+/// Which changelog changes are worth announcing.
+#[derive(Debug, Clone, Default)]
+pub struct NotifyFilter {
+    /// Top-level fields to announce; empty means every field.
+    fields: HashSet<String>,
+}
+
+impl NotifyFilter {
+    pub fn new(fields: &[String]) -> Self {
+        Self { fields: fields.iter().cloned().collect() }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(&WORLD_EVENT_NOTIFY_FIELDS)
+    }
+
+    /// Whether one `changes` entry of a changelog doc should be announced.
+    /// Text edits that only touch whitespace never are.
+    fn wants(&self, change: &Document) -> bool {
+        let field = change.get_str("field").unwrap_or("");
+        if !self.fields.is_empty() && !self.fields.contains(field) {
+            return false;
+        }
+        match (change.get("before"), change.get("after")) {
+            (Some(Bson::String(a)), Some(Bson::String(b))) => {
+                !a.split_whitespace().eq(b.split_whitespace())
+            }
+            _ => true,
+        }
+    }
+}
+
+/// A changelog entry rendered for the notification stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub internal_name: String,
+    pub event_name: String,
+    pub kind: String,
+    pub summary: String,
+    /// The announced subset of the entry's `changes`.
+    pub changes: Vec<Bson>,
+    /// RFC 3339, for consumers outside Mongo.
+    pub changed_at: String,
+}
+
+/// Render a `world_event_changelog` doc, or `None` when every change it
+/// records is filtered out. Removals and reactivations are always announced.
+/// Pure.
+pub fn notification(entry: &Document, filter: &NotifyFilter) -> Option<Notification> {
+    let internal_name = entry.get_str("internalName").ok()?;
+    let event_name = entry.get_str("eventName").unwrap_or(internal_name);
+    let kind = entry.get_str("kind").ok()?;
+
+    let changes: Vec<Bson> = entry
+        .get_array("changes")
+        .map(|arr| {
+            arr.iter()
+                .filter(|c| c.as_document().is_some_and(|c| filter.wants(c)))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let summary = match kind {
+        "removed" => format!("{event_name} was removed"),
+        "reactivated" => format!("{event_name} is back"),
+        _ if changes.is_empty() => return None,
+        _ => {
+            let lines: Vec<String> = changes
+                .iter()
+                .filter_map(|c| c.as_document().map(render_change))
+                .collect();
+            format!("{event_name} changed:\n{}", lines.join("\n"))
+        }
+    };
+
+    Some(Notification {
+        internal_name: internal_name.to_string(),
+        event_name: event_name.to_string(),
+        kind: kind.to_string(),
+        summary,
+        changes,
+        changed_at: entry
+            .get_datetime("changedAt")
+            .ok()
+            .and_then(|at| at.try_to_rfc3339_string().ok())
+            .unwrap_or_default(),
+    })
+}
+
+/// `- rewardPerLevel/10/2: 32x Liquid Emerald → 64x Liquid Emerald`
+fn render_change(change: &Document) -> String {
+    let path = change
+        .get_str("path")
+        .unwrap_or("")
+        .trim_start_matches('/')
+        .replace("~1", "/")
+        .replace("~0", "~");
+    let value = |k: &str| render_value(change.get(k).unwrap_or(&Bson::Null));
+    match change.get_str("op").unwrap_or("") {
+        "add" => format!("- {path}: added {}", value("after")),
+        "remove" => format!("- {path}: removed {}", value("before")),
+        _ => format!("- {path}: {} → {}", value("before"), value("after")),
+    }
+}
+
+fn render_value(value: &Bson) -> String {
+    match value {
+        Bson::Null => "none".to_string(),
+        Bson::String(s) => s.clone(),
+        Bson::Int32(n) => n.to_string(),
+        Bson::Int64(n) => n.to_string(),
+        Bson::Double(n) => n.to_string(),
+        Bson::Boolean(b) => b.to_string(),
+        Bson::Array(items) => items.iter().map(render_value).collect::<Vec<_>>().join(", "),
+        // Typed rewards read best as the upstream text or the item name
+        Bson::Document(d) => match (d.get_str("text"), d.get_str("name"), d.get("quantity")) {
+            (Ok(text), _, _) => text.to_string(),
+            (_, Ok(name), Some(Bson::Int64(q))) => format!("{q}x {name}"),
+            (_, Ok(name), _) => name.to_string(),
+            _ => value.clone().into_relaxed_extjson().to_string(),
+        },
+        other => other.clone().into_relaxed_extjson().to_string(),
+    }
+}
+
+/// Append the notification-worthy changelog entries to the Redis stream.
+/// Returns how many were published.
+pub async fn publish_notifications(entries: &[Document], filter: &NotifyFilter) -> Result<usize> {
+    let mut published = 0usize;
+    for n in entries.iter().filter_map(|e| notification(e, filter)) {
+        let changes = Bson::Array(n.changes).into_relaxed_extjson().to_string();
+        stream_add(
+            &WORLD_EVENT_STREAM,
+            *WORLD_EVENT_STREAM_MAXLEN,
+            &[
+                ("internalName", n.internal_name),
+                ("eventName", n.event_name),
+                ("kind", n.kind),
+                ("summary", n.summary),
+                ("changes", changes),
+                ("changedAt", n.changed_at),
+            ],
+        )
+        .await?;
+        published += 1;
+    }
+    Ok(published)
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{doc, DateTime};
+
+    use super::*;
+
+    fn entry(kind: &str, changes: Vec<Document>) -> Document {
+        doc! {
+            "internalName": "Corrupted Spire",
+            "eventName": "The Corrupted Spire",
+            "kind": kind,
+            "changes": changes,
+            "changedAt": DateTime::from_millis(1_700_000_000_000),
+        }
+    }
+
+    fn change(path: &str, op: &str, before: Bson, after: Bson) -> Document {
+        let field = path.trim_start_matches('/').split('/').next().unwrap();
+        doc! { "field": field, "path": path, "op": op, "before": before, "after": after }
+    }
+
+    #[test]
+    fn renders_changes_as_a_readable_summary() {
+        let e = entry(
+            "changed",
+            vec![
+                change("/level", "change", Bson::Int64(80), Bson::Int64(85)),
+                change(
+                    "/rewardPerLevel/10/2",
+                    "add",
+                    Bson::Null,
+                    Bson::Document(doc! { "kind": "item", "name": "Liquid Emerald", "quantity": 64_i64, "text": Bson::Null }),
+                ),
+            ],
+        );
+        let n = notification(&e, &NotifyFilter::default()).unwrap();
+
+        assert_eq!(
+            n.summary,
+            "The Corrupted Spire changed:\n- level: 80 → 85\n- rewardPerLevel/10/2: added 64x Liquid Emerald"
+        );
+        assert_eq!(n.changes.len(), 2);
+        assert_eq!(n.changed_at, "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn filters_unlisted_fields_and_whitespace_edits() {
+        let e = entry(
+            "changed",
+            vec![
+                change("/lore", "change", Bson::String("A  tower.".into()), Bson::String("A tower.\n".into())),
+                change("/length", "change", Bson::String("Short".into()), Bson::String("Long".into())),
+            ],
+        );
+
+        let everything = notification(&e, &NotifyFilter::default()).unwrap();
+        assert_eq!(everything.summary, "The Corrupted Spire changed:\n- length: Short → Long");
+
+        let rewards_only = NotifyFilter::new(&["rewardPerLevel".to_string()]);
+        assert_eq!(notification(&e, &rewards_only), None);
+    }
+
+    #[test]
+    fn removals_are_always_announced() {
+        let rewards_only = NotifyFilter::new(&["rewardPerLevel".to_string()]);
+        let n = notification(&entry("removed", vec![]), &rewards_only).unwrap();
+        assert_eq!(n.summary, "The Corrupted Spire was removed");
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::DateTime;
+use mongodb::bson::{doc, Bson, DateTime as BsonDateTime, Document};
+
+pub const SCHEDULES_COLLECTION: &str = "world_event_schedules";
+
+// A schedule value is kept for 24 hours after it was last observed
+pub const SCHEDULE_TTL_SECS: i64 = 60 * 60 * 24;
+
+/// Parse a Wynncraft schedule string to epoch milliseconds.
+/// Tries RFC3339 first, then a bare unix-seconds/millis number.
+/// Returns None for unrecognised formats — callers treat that as "skip".
+pub fn parse_schedule_to_ms(s: &str) -> Option<i64> {
+    let trimmed = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(trimmed) {
+        return Some(dt.timestamp_millis());
+    }
+    if let Ok(n) = trimmed.parse::<i64>() {
+        // Heuristic: values < 1e11 are seconds, else milliseconds.
+        return Some(if n < 100_000_000_000 { n * 1000 } else { n });
+    }
+    None
+}
+
+/// Upsert statement recording that `internal_name` currently shows `schedule`.
+///
+/// One doc per distinct (event, schedule value): the first poll showing a value
+/// inserts it with `firstObservedAt`; every poll moves `lastObservedAt` and the
+/// expiry forward. `scheduleAt` is set on every poll too, so docs written
+/// before it existed gain it while their value is still shown.
+pub fn snapshot_statement(internal_name: &str, schedule: Option<&str>, now_ts: i64) -> Document {
+    let now = BsonDateTime::from_millis(now_ts * 1000);
+    let schedule_at = schedule
+        .and_then(parse_schedule_to_ms)
+        .map(|ms| Bson::DateTime(BsonDateTime::from_millis(ms)))
+        .unwrap_or(Bson::Null);
+    let schedule = schedule.map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null);
+
+    doc! {
+        "q": { "internalName": internal_name, "schedule": schedule.clone() },
+        "u": {
+            "$setOnInsert": {
+                "internalName": internal_name,
+                "schedule": schedule,
+                "firstObservedAt": now,
+            },
+            "$set": {
+                "scheduleAt": schedule_at,
+                "lastObservedAt": now,
+                "expireAt": BsonDateTime::from_millis((now_ts + SCHEDULE_TTL_SECS) * 1000),
+            },
+        },
+        "upsert": true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339_and_unix_times() {
+        assert_eq!(parse_schedule_to_ms("2023-11-14T22:13:20Z"), Some(1_700_000_000_000));
+        assert_eq!(parse_schedule_to_ms("1700000000"), Some(1_700_000_000_000));
+        assert_eq!(parse_schedule_to_ms("1700000000000"), Some(1_700_000_000_000));
+        assert_eq!(parse_schedule_to_ms("soon"), None);
+    }
+
+    #[test]
+    fn snapshot_sets_parsed_schedule_on_every_poll() {
+        let stmt = snapshot_statement("Prelude to Annihilation", Some("2023-11-14T22:13:20Z"), 1_699_990_000);
+        let u = stmt.get_document("u").unwrap();
+
+        assert_eq!(
+            u.get_document("$set").unwrap().get_datetime("scheduleAt").unwrap().timestamp_millis(),
+            1_700_000_000_000
+        );
+        assert_eq!(
+            u.get_document("$set").unwrap().get_datetime("lastObservedAt").unwrap().timestamp_millis(),
+            1_699_990_000_000
+        );
+        assert!(!u.get_document("$setOnInsert").unwrap().contains_key("scheduleAt"));
+        assert_eq!(stmt.get_document("q").unwrap().get_str("schedule").unwrap(), "2023-11-14T22:13:20Z");
+    }
+}
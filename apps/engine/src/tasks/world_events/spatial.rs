@@ -0,0 +1,255 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde_json::Value;
+use tracing::{info_span, Instrument};
+
+use crate::logger::log_error;
+use crate::scheduler::failure::UnexpectedPayload;
+
+const TERRITORIES_URL: &str = "https://api.wynncraft.com/v3/guild/list/territory";
+
+// Territory borders rarely move; refetch a few times a day
+const TERRITORY_REFRESH: Duration = Duration::from_secs(60 * 60 * 6);
+
+// Bounds of the `2d` index; the default is longitude/latitude (±180). A point
+// outside them fails the whole write, so such points are never stored
+const MAP_MIN: f64 = -20_000.0;
+const MAP_MAX: f64 = 20_000.0;
+
+fn within_map(x: i64, z: i64) -> bool {
+    let bounds = MAP_MIN..MAP_MAX;
+    bounds.contains(&(x as f64)) && bounds.contains(&(z as f64))
+}
+
+/// A territory's axis-aligned rectangle on the x/z plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Territory {
+    pub name: String,
+    pub min_x: i64,
+    pub max_x: i64,
+    pub min_z: i64,
+    pub max_z: i64,
+}
+
+impl Territory {
+    fn contains(&self, x: i64, z: i64) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_z..=self.max_z).contains(&z)
+    }
+
+    fn area(&self) -> i64 {
+        (self.max_x - self.min_x) * (self.max_z - self.min_z)
+    }
+}
+
+/// Parse the `/v3/guild/list/territory` body: territory name → `location`
+/// with `start`/`end` corners as `[x, z]`, in either order.
+pub fn parse_territories(body: &Value) -> Vec<Territory> {
+    let Some(obj) = body.as_object() else {
+        return Vec::new();
+    };
+    obj.iter()
+        .filter_map(|(name, t)| {
+            let corner = |k: &str| -> Option<(i64, i64)> {
+                let c = t["location"][k].as_array()?;
+                Some((c.first()?.as_i64()?, c.get(1)?.as_i64()?))
+            };
+            let ((x1, z1), (x2, z2)) = (corner("start")?, corner("end")?);
+            Some(Territory {
+                name: name.clone(),
+                min_x: x1.min(x2),
+                max_x: x1.max(x2),
+                min_z: z1.min(z2),
+                max_z: z1.max(z2),
+            })
+        })
+        .collect()
+}
+
+/// The territory containing (x, z); the smallest one when rectangles overlap.
+pub fn territory_at(territories: &[Territory], x: i64, z: i64) -> Option<&str> {
+    territories
+        .iter()
+        .filter(|t| t.contains(x, z))
+        .min_by_key(|t| t.area())
+        .map(|t| t.name.as_str())
+}
+
+struct TerritoryCache {
+    territories: Vec<Territory>,
+    fetched_at: Option<Instant>,
+}
+
+static CACHE: Lazy<Mutex<TerritoryCache>> =
+    Lazy::new(|| Mutex::new(TerritoryCache { territories: Vec::new(), fetched_at: None }));
+
+async fn fetch_territories(client: &Client) -> Result<Vec<Territory>> {
+    let body: Value = async {
+        client
+            .get(TERRITORIES_URL)
+            .send()
+            .await
+            .context("requesting /v3/guild/list/territory")?
+            .error_for_status()
+            .context("requesting /v3/guild/list/territory")?
+            .json()
+            .await
+            .context("decoding /v3/guild/list/territory response")
+    }
+    .instrument(info_span!("http.fetch", url = TERRITORIES_URL))
+    .await?;
+
+    let territories = parse_territories(&body);
+    if territories.is_empty() {
+        return Err(UnexpectedPayload("Expected territories with locations").into());
+    }
+    Ok(territories)
+}
+
+/// Territory rectangles, refreshed every `TERRITORY_REFRESH`.
+///
+/// Labels are derived data, so a failed refresh keeps the previous list (and
+/// is logged) rather than failing the world events task or blanking every
+/// label.
+pub async fn territories(client: &Client) -> Vec<Territory> {
+    let stale = {
+        let cache = CACHE.lock().unwrap();
+        cache.fetched_at.is_none_or(|at| at.elapsed() >= TERRITORY_REFRESH)
+    };
+    if stale {
+        match fetch_territories(client).await {
+            Ok(fresh) => {
+                let mut cache = CACHE.lock().unwrap();
+                cache.territories = fresh;
+                cache.fetched_at = Some(Instant::now());
+            }
+            Err(e) => log_error("world events: refreshing territories failed", &e, None),
+        }
+    }
+    CACHE.lock().unwrap().territories.clone()
+}
+
+/// Add the spatial fields to a static event doc: `spots`, one
+/// `{ point: [x, z], territory }` per location with event coordinates (the
+/// `2d` index covers `spots.point`), and `territory`, the first known label.
+/// Coordinates outside the index bounds get no spot.
+///
+/// Kept beside `location` rather than inside it so a territory border moving
+/// upstream does not show up as an event change.
+pub fn annotate_locations(event_doc: &mut Document, territories: &[Territory]) {
+    let mut spots: Vec<Bson> = Vec::new();
+    let mut first_territory: Option<String> = None;
+
+    if let Ok(locations) = event_doc.get_array("location") {
+        for loc in locations {
+            let coords = loc
+                .as_document()
+                .and_then(|l| l.get_document("event").ok())
+                .and_then(|c| Some((c.get_i64("x").ok()?, c.get_i64("z").ok()?)));
+            let Some((x, z)) = coords.filter(|&(x, z)| within_map(x, z)) else {
+                continue;
+            };
+            let territory = territory_at(territories, x, z).map(str::to_string);
+            if first_territory.is_none() {
+                first_territory = territory.clone();
+            }
+            spots.push(Bson::Document(doc! {
+                "point": [x as f64, z as f64],
+                "territory": territory.map(Bson::String).unwrap_or(Bson::Null),
+            }));
+        }
+    }
+
+    event_doc.insert("spots", spots);
+    event_doc.insert("territory", first_territory.map(Bson::String).unwrap_or(Bson::Null));
+}
+
+/// `2d` index over `spots.point` (x, z), for "events near here" queries.
+pub async fn ensure_spatial_index(coll: &Collection<Document>) -> Result<()> {
+    let idx = IndexModel::builder()
+        .keys(doc! { "spots.point": "2d" })
+        .options(IndexOptions::builder().min(MAP_MIN).max(MAP_MAX).build())
+        .build();
+    coll.create_index(idx, None)
+        .await
+        .context("creating world_events 2d index")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn territories() -> Vec<Territory> {
+        parse_territories(&json!({
+            "Ragni": { "location": { "start": [-955, -1415], "end": [-756, -1748] } },
+            "Ragni Main Entrance": { "location": { "start": [-900, -1500], "end": [-850, -1450] } },
+            "Detlas": { "location": { "start": [289, -1513], "end": [567, -1636] } },
+            "Broken": { "guild": {} },
+        }))
+    }
+
+    #[test]
+    fn parses_corners_in_either_order() {
+        let parsed = territories();
+        assert_eq!(parsed.len(), 3);
+        let ragni = parsed.iter().find(|t| t.name == "Ragni").unwrap();
+        assert_eq!((ragni.min_x, ragni.max_x, ragni.min_z, ragni.max_z), (-955, -756, -1748, -1415));
+    }
+
+    #[test]
+    fn picks_smallest_containing_territory() {
+        let t = territories();
+        assert_eq!(territory_at(&t, -880, -1480), Some("Ragni Main Entrance"));
+        assert_eq!(territory_at(&t, -800, -1600), Some("Ragni"));
+        assert_eq!(territory_at(&t, 0, 0), None);
+    }
+
+    #[test]
+    fn annotates_spots_with_territories() {
+        let mut event = doc! { "location": [
+            { "event": Bson::Null },
+            { "event": { "x": 0_i64, "y": 70_i64, "z": 0_i64 } },
+            { "event": { "x": 300_i64, "y": 70_i64, "z": -1600_i64 } },
+        ]};
+        annotate_locations(&mut event, &territories());
+
+        let spots = event.get_array("spots").unwrap();
+        assert_eq!(spots.len(), 2);
+        let first = spots[0].as_document().unwrap();
+        assert_eq!(first.get("territory"), Some(&Bson::Null));
+        assert_eq!(first.get_array("point").unwrap(), &vec![Bson::Double(0.0), Bson::Double(0.0)]);
+        assert_eq!(spots[1].as_document().unwrap().get_str("territory").unwrap(), "Detlas");
+        // The first spot inside a known territory labels the event
+        assert_eq!(event.get_str("territory").unwrap(), "Detlas");
+    }
+
+    #[test]
+    fn points_outside_index_bounds_get_no_spot() {
+        let mut event = doc! { "location": [
+            { "event": { "x": 25_000_i64, "y": 70_i64, "z": 0_i64 } },
+            { "event": { "x": 0_i64, "y": 70_i64, "z": -20_000_i64 } },
+            { "event": { "x": 0_i64, "y": 70_i64, "z": 20_000_i64 } },
+            { "event": { "x": 300_i64, "y": 70_i64, "z": -1600_i64 } },
+        ]};
+        annotate_locations(&mut event, &territories());
+
+        let spots = event.get_array("spots").unwrap();
+        assert_eq!(spots.len(), 2);
+        assert_eq!(
+            spots[0].as_document().unwrap().get_array("point").unwrap(),
+            &vec![Bson::Double(0.0), Bson::Double(-20_000.0)]
+        );
+        assert_eq!(spots[1].as_document().unwrap().get_str("territory").unwrap(), "Detlas");
+    }
+}
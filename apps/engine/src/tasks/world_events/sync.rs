@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use mongodb::bson::{doc, DateTime as BsonDateTime, Document};
+
+use super::diff::{diff_event_docs, FieldChange};
+use super::model::upgrade_legacy;
+
+/// Fields computed by the engine rather than reported upstream (see
+/// `spatial::annotate_locations`). Kept current without changelog entries.
+const DERIVED_FIELDS: &[&str] = &["spots", "territory"];
+
+/// One event as fetched this tick, already converted to its static doc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedEvent {
+    pub internal_name: String,
+    pub doc: Document,
+}
+
+/// Everything a tick wants written, computed without touching the DB.
+#[derive(Debug, Default, PartialEq)]
+pub struct EventWrites {
+    /// `update` command statements for `world_events`.
+    pub updates: Vec<Document>,
+    /// Docs for `world_event_changelog`.
+    pub changelog: Vec<Document>,
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub reactivated: usize,
+}
+
+fn changelog_entry(
+    internal_name: &str,
+    event_name: &str,
+    kind: &str,
+    changes: &[FieldChange],
+    changed_at: BsonDateTime,
+) -> Document {
+    doc! {
+        "internalName": internal_name,
+        "eventName": event_name,
+        "kind": kind,
+        "changes": changes.iter().map(FieldChange::to_bson).collect::<Vec<_>>(),
+        "changedAt": changed_at,
+    }
+}
+
+/// Reconcile fetched events against the stored `world_events` docs.
+///
+/// New events are upserted active; changed or previously removed ones are
+/// rewritten and reactivated; stored events missing from a non-empty fetch are
+/// marked `active: false` with `removedAt`. Each change, reactivation and
+/// removal gets a changelog entry. Stored events are visited in name order so
+/// the output is stable. Pure.
+pub fn reconcile_events(
+    fetched: &[FetchedEvent],
+    existing: &HashMap<String, Document>,
+    now_ts: i64,
+) -> EventWrites {
+    let mut writes = EventWrites::default();
+    let now = BsonDateTime::from_millis(now_ts * 1000);
+
+    for event in fetched {
+        let name = &event.internal_name;
+        let event_name = event.doc.get_str("name").ok().filter(|n| !n.is_empty()).unwrap_or("Unknown");
+        let mut set = event.doc.clone();
+        set.insert("active", true);
+
+        let Some(old_doc) = existing.get(name) else {
+            writes.updates.push(doc! {
+                "q": { "internalName": name },
+                "u": { "$set": set },
+                "upsert": true,
+            });
+            writes.added += 1;
+            continue;
+        };
+
+        // Docs stored in an older representation, before a derived field
+        // existed, or labelled with an older territory list are brought up to
+        // date silently
+        let upgraded = upgrade_legacy(old_doc);
+        let changes = diff_event_docs(&upgraded, &event.doc);
+        let was_removed = old_doc.get_bool("active") == Ok(false);
+        if changes.is_empty() && !was_removed {
+            let stale = upgraded != *old_doc || DERIVED_FIELDS.iter().any(|f| old_doc.get(*f) != event.doc.get(*f));
+            if stale {
+                writes.updates.push(doc! {
+                    "q": { "internalName": name },
+                    "u": { "$set": set },
+                });
+            }
+            writes.unchanged += 1;
+            continue;
+        }
+
+        // Reappearing events become active again
+        writes.updates.push(doc! {
+            "q": { "internalName": name },
+            "u": { "$set": set, "$unset": { "removedAt": "" } },
+            "upsert": true,
+        });
+        if was_removed {
+            writes.changelog.push(changelog_entry(name, event_name, "reactivated", &[], now));
+            writes.reactivated += 1;
+        }
+        if !changes.is_empty() {
+            writes.changelog.push(changelog_entry(name, event_name, "changed", &changes, now));
+            writes.changed += 1;
+        }
+    }
+
+    // An empty list is far more likely an upstream hiccup than every event
+    // being retired at once, so it never removes anything.
+    if fetched.is_empty() {
+        return writes;
+    }
+    let seen: HashSet<&String> = fetched.iter().map(|e| &e.internal_name).collect();
+    let stored: BTreeMap<&String, &Document> = existing.iter().collect();
+    for (name, old_doc) in stored {
+        if old_doc.get_bool("active") == Ok(false) || seen.contains(name) {
+            continue;
+        }
+        writes.updates.push(doc! {
+            "q": { "internalName": name },
+            "u": { "$set": { "active": false, "removedAt": now } },
+        });
+        let event_name = old_doc.get_str("name").unwrap_or("Unknown");
+        writes.changelog.push(changelog_entry(name, event_name, "removed", &[], now));
+        writes.removed += 1;
+    }
+
+    writes
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::Bson;
+
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn event(name: &str, level: i64) -> FetchedEvent {
+        FetchedEvent {
+            internal_name: name.into(),
+            doc: doc! { "name": name, "internalName": name, "level": level },
+        }
+    }
+
+    fn stored(name: &str, level: i64, active: Option<bool>) -> (String, Document) {
+        let mut d = doc! { "name": name, "internalName": name, "level": level };
+        if let Some(active) = active {
+            d.insert("active", active);
+        }
+        (name.to_string(), d)
+    }
+
+    fn kinds(writes: &EventWrites) -> Vec<(&str, &str)> {
+        writes
+            .changelog
+            .iter()
+            .map(|c| (c.get_str("internalName").unwrap(), c.get_str("kind").unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_added_changed_unchanged_and_removed() {
+        let existing = HashMap::from([
+            stored("Changed", 10, Some(true)),
+            stored("Gone", 10, None),
+            stored("Same", 10, Some(true)),
+        ]);
+        let writes = reconcile_events(
+            &[event("Changed", 20), event("New", 1), event("Same", 10)],
+            &existing,
+            NOW,
+        );
+
+        assert_eq!((writes.added, writes.changed, writes.unchanged, writes.removed), (1, 1, 1, 1));
+        assert_eq!(writes.updates.len(), 3);
+        assert_eq!(kinds(&writes), vec![("Changed", "changed"), ("Gone", "removed")]);
+    }
+
+    #[test]
+    fn reappearing_event_is_reactivated() {
+        let existing = HashMap::from([stored("Back", 10, Some(false))]);
+        let writes = reconcile_events(&[event("Back", 10)], &existing, NOW);
+
+        assert_eq!(writes.reactivated, 1);
+        assert_eq!(kinds(&writes), vec![("Back", "reactivated")]);
+        let u = writes.updates[0].get_document("u").unwrap();
+        assert!(u.get_document("$set").unwrap().get_bool("active").unwrap());
+        assert!(u.get_document("$unset").unwrap().contains_key("removedAt"));
+    }
+
+    #[test]
+    fn missing_derived_fields_are_written_without_changelog() {
+        // "Spire" was stored before spatial fields existed; "Labelled" is current
+        let (name, mut stored_labelled) = stored("Labelled", 10, Some(true));
+        stored_labelled.insert("spots", Vec::<Bson>::new());
+        stored_labelled.insert("territory", Bson::Null);
+        let existing = HashMap::from([stored("Spire", 10, Some(true)), (name, stored_labelled)]);
+
+        let mut labelled = event("Labelled", 10);
+        labelled.doc.insert("spots", Vec::<Bson>::new());
+        labelled.doc.insert("territory", Bson::Null);
+
+        let mut spire = event("Spire", 10);
+        spire.doc.insert("spots", vec![Bson::Document(doc! { "point": [300.0, -1600.0], "territory": "Detlas" })]);
+        spire.doc.insert("territory", "Detlas");
+        let writes = reconcile_events(&[spire, labelled], &existing, NOW);
+
+        assert_eq!(writes.unchanged, 2);
+        assert!(writes.changelog.is_empty());
+        assert_eq!(writes.updates.len(), 1);
+        assert_eq!(writes.updates[0].get_document("q").unwrap().get_str("internalName").unwrap(), "Spire");
+        let set = writes.updates[0].get_document("u").unwrap().get_document("$set").unwrap();
+        assert_eq!(set.get_str("territory").unwrap(), "Detlas");
+    }
+
+    #[test]
+    fn legacy_representation_is_rewritten_without_changelog() {
+        let (name, mut legacy) = stored("Spire", 10, Some(true));
+        legacy.insert("requirements", vec![Bson::Document(doc! { "type": "level", "value": 80_i64 })]);
+        let existing = HashMap::from([(name, legacy)]);
+
+        let mut spire = event("Spire", 10);
+        spire.doc.insert(
+            "requirements",
+            vec![Bson::Document(doc! { "type": "level", "value": 80_i64, "kind": "level", "minLevel": 80_i64 })],
+        );
+        let writes = reconcile_events(&[spire], &existing, NOW);
+
+        assert_eq!((writes.changed, writes.unchanged), (0, 1));
+        assert!(writes.changelog.is_empty());
+        assert_eq!(writes.updates.len(), 1);
+    }
+
+    #[test]
+    fn empty_fetch_removes_nothing() {
+        let existing = HashMap::from([stored("Kept", 10, Some(true))]);
+        assert_eq!(reconcile_events(&[], &existing, NOW), EventWrites::default());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, DateTime as BsonDateTime, Document},
+    Database, IndexModel,
+};
+use tracing::{info_span, Instrument};
+
+use crate::mongo_client::run_write_command;
+
+const TIMELINE_COLLECTION: &str = "world_event_timeline";
+const PREDICTIONS_COLLECTION: &str = "world_event_predictions";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineSource {
+    /// The upstream `schedule` of the event.
+    Observed,
+    /// Projected by the predictor in `world_event_predictions`.
+    Predicted,
+}
+
+impl TimelineSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimelineSource::Observed => "observed",
+            TimelineSource::Predicted => "predicted",
+        }
+    }
+}
+
+/// The predictor's view of one event, as read from `world_event_predictions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    pub predicted_ms: Vec<i64>,
+    pub mean_interval_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    pub internal_name: String,
+    pub start_ms: i64,
+    pub source: TimelineSource,
+}
+
+impl TimelineEntry {
+    fn id(&self) -> String {
+        format!("{}|{}|{}", self.internal_name, self.source.as_str(), self.start_ms)
+    }
+}
+
+/// Upcoming occurrences, soonest first.
+///
+/// Observed schedules always win. A prediction earlier than half an interval
+/// past an observed occurrence describes that same occurrence and is dropped.
+/// Pure.
+pub fn build_timeline(
+    observed: &BTreeMap<String, i64>,
+    predictions: &BTreeMap<String, Prediction>,
+    now_ms: i64,
+) -> Vec<TimelineEntry> {
+    let mut entries: Vec<TimelineEntry> = observed
+        .iter()
+        .filter(|(_, start)| **start > now_ms)
+        .map(|(name, start)| TimelineEntry {
+            internal_name: name.clone(),
+            start_ms: *start,
+            source: TimelineSource::Observed,
+        })
+        .collect();
+
+    for (name, prediction) in predictions {
+        let covered_until = observed
+            .get(name)
+            .filter(|start| **start > now_ms)
+            .map(|start| start + prediction.mean_interval_ms / 2);
+        for start in &prediction.predicted_ms {
+            if *start <= now_ms || covered_until.is_some_and(|until| *start <= until) {
+                continue;
+            }
+            entries.push(TimelineEntry {
+                internal_name: name.clone(),
+                start_ms: *start,
+                source: TimelineSource::Predicted,
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| a.start_ms.cmp(&b.start_ms).then_with(|| a.internal_name.cmp(&b.internal_name)));
+    entries
+}
+
+/// Rebuild `world_event_timeline` from this tick's schedules and the stored
+/// predictions. Entries no longer upcoming (passed, or superseded by a newer
+/// prediction) are deleted. `static_docs` supplies name, location, territory,
+/// level and difficulty per event.
+pub async fn update_timeline(
+    db: &Database,
+    static_docs: &HashMap<String, Document>,
+    observed: &BTreeMap<String, i64>,
+    now_ms: i64,
+) -> Result<usize> {
+    let coll = db.collection::<Document>(TIMELINE_COLLECTION);
+    let idx = IndexModel::builder().keys(doc! { "start": 1 }).build();
+    let _ = coll
+        .create_index(idx, None)
+        .await
+        .context("creating world_event_timeline index")?;
+
+    let prediction_docs: Vec<Document> = async {
+        db.collection::<Document>(PREDICTIONS_COLLECTION)
+            .find(doc! { "internalName": { "$type": "string" } }, None)
+            .await?
+            .try_collect()
+            .await
+    }
+    .instrument(info_span!("mongo.find", collection = PREDICTIONS_COLLECTION))
+    .await
+    .context("loading world event predictions")?;
+
+    let mut predictions: BTreeMap<String, Prediction> = BTreeMap::new();
+    for d in &prediction_docs {
+        let Ok(name) = d.get_str("internalName") else {
+            continue;
+        };
+        let predicted_ms = d
+            .get_array("predicted")
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|p| p.as_document()?.get_i64("datetime_utc").ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        predictions.insert(
+            name.to_string(),
+            Prediction {
+                predicted_ms,
+                mean_interval_ms: d.get_i64("meanIntervalMs").unwrap_or(0),
+            },
+        );
+    }
+
+    let entries = build_timeline(observed, &predictions, now_ms);
+    let ids: Vec<Bson> = entries.iter().map(|e| Bson::String(e.id())).collect();
+
+    run_write_command(
+        db,
+        doc! {
+            "delete": TIMELINE_COLLECTION,
+            "deletes": [{ "q": { "_id": { "$nin": ids } }, "limit": 0 }],
+        },
+    )
+    .instrument(info_span!("mongo.bulk_delete", collection = TIMELINE_COLLECTION))
+    .await
+    .context("pruning world event timeline")?;
+
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    let updates: Vec<Document> = entries
+        .iter()
+        .map(|e| {
+            let static_doc = static_docs.get(&e.internal_name);
+            let field = |k: &str| static_doc.and_then(|d| d.get(k)).cloned().unwrap_or(Bson::Null);
+            doc! {
+                "q": { "_id": e.id() },
+                "u": { "$set": {
+                    "internalName": &e.internal_name,
+                    "name": field("name"),
+                    "start": BsonDateTime::from_millis(e.start_ms),
+                    "source": e.source.as_str(),
+                    "location": field("location"),
+                    "territory": field("territory"),
+                    "level": field("level"),
+                    "difficulty": field("difficulty"),
+                }},
+                "upsert": true,
+            }
+        })
+        .collect();
+    let count = updates.len();
+    run_write_command(db, doc! { "update": TIMELINE_COLLECTION, "updates": updates, "ordered": false })
+        .instrument(info_span!("mongo.bulk_update", collection = TIMELINE_COLLECTION, count))
+        .await
+        .context("upserting world event timeline")?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_MS: i64 = 1_700_000_000_000;
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    #[test]
+    fn observed_schedule_replaces_matching_prediction() {
+        let observed = BTreeMap::from([("Annihilation".to_string(), NOW_MS + HOUR_MS)]);
+        let predictions = BTreeMap::from([(
+            "Annihilation".to_string(),
+            Prediction {
+                predicted_ms: vec![NOW_MS + 2 * HOUR_MS, NOW_MS + 76 * HOUR_MS],
+                mean_interval_ms: 76 * HOUR_MS,
+            },
+        )]);
+
+        let entries = build_timeline(&observed, &predictions, NOW_MS);
+        assert_eq!(
+            entries,
+            vec![
+                TimelineEntry {
+                    internal_name: "Annihilation".into(),
+                    start_ms: NOW_MS + HOUR_MS,
+                    source: TimelineSource::Observed,
+                },
+                TimelineEntry {
+                    internal_name: "Annihilation".into(),
+                    start_ms: NOW_MS + 76 * HOUR_MS,
+                    source: TimelineSource::Predicted,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn past_occurrences_are_pruned_and_order_is_by_start() {
+        let observed = BTreeMap::from([
+            ("Past".to_string(), NOW_MS - HOUR_MS),
+            ("Later".to_string(), NOW_MS + 3 * HOUR_MS),
+            ("Sooner".to_string(), NOW_MS + HOUR_MS),
+        ]);
+        let predictions = BTreeMap::from([(
+            "Past".to_string(),
+            Prediction { predicted_ms: vec![NOW_MS - 1, NOW_MS + 2 * HOUR_MS], mean_interval_ms: HOUR_MS },
+        )]);
+
+        let entries = build_timeline(&observed, &predictions, NOW_MS);
+        let order: Vec<(&str, TimelineSource)> =
+            entries.iter().map(|e| (e.internal_name.as_str(), e.source)).collect();
+        assert_eq!(
+            order,
+            vec![
+                ("Sooner", TimelineSource::Observed),
+                ("Past", TimelineSource::Predicted),
+                ("Later", TimelineSource::Observed),
+            ]
+        );
+    }
+}
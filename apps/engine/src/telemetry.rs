@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::OTLP_ENDPOINT;
+
+/// Install the OTLP trace exporter if a collector endpoint is configured.
+///
+/// Returns the endpoint traces are sent to, or `None` when tracing is off —
+/// spans are then created but dropped without ever leaving the process.
+pub fn init() -> Result<Option<&'static str>> {
+    let Some(endpoint) = OTLP_ENDPOINT.as_deref() else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .context("building OTLP span exporter")?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("wynnpool-engine").build())
+        .build();
+    let tracer = provider.tracer("wynnpool-engine");
+    opentelemetry::global::set_tracer_provider(provider);
+
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .context("installing tracing subscriber")?;
+
+    Ok(Some(endpoint))
+}